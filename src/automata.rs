@@ -0,0 +1,270 @@
+//! Multi-state cellular automata, and the `Automaton` trait shared with `LifeLike`.
+//!
+//! Cells are plain `u8` state numbers, so grids of them work with snapshots, replays and
+//! `Palette` colouring as they are.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::buf::{BufferError, Publisher, TwoDeeBuffer, TwoDeeRead};
+use crate::colour::{lerp_rgba, Palette};
+use crate::life::{LifeRule, RuleError};
+use crate::neighbours::{Edge, Neighbourhood};
+use crate::pack_rgba;
+
+/// A rule working out each frame from the one before.
+pub trait Automaton<C: Copy> {
+    /// Writes the frame after `src` into `dst`, which must be the same size.
+    fn step<S, D>(&self, src: &S, dst: &mut D) -> Result<(), BufferError>
+    where
+        S: TwoDeeRead<C> + ?Sized,
+        D: TwoDeeBuffer<C> + ?Sized;
+
+    /// Colours for showing each state.
+    fn palette(&self) -> Palette;
+
+    /// Advances the published frame of `buf` by one step.
+    fn tick<P: Publisher<C>>(&self, buf: &mut P) {
+        buf.step(|front, back| {
+            self.step(front, back)
+                .expect("front and back buffers are the same size")
+        });
+    }
+}
+
+/// Sets every cell of `dst` to `next(cell, n)`, where `n` counts the cell's Moore neighbours
+/// in `src` that `counts` picks out.
+pub(crate) fn step_by_count<C, S, D>(
+    src: &S,
+    dst: &mut D,
+    edge: Edge<C>,
+    counts: impl Fn(&C) -> bool,
+    next: impl Fn(C, usize) -> C,
+) -> Result<(), BufferError>
+where
+    C: Copy,
+    S: TwoDeeRead<C> + ?Sized,
+    D: TwoDeeBuffer<C> + ?Sized,
+{
+    if (src.width(), src.height()) != (dst.width(), dst.height()) {
+        return Err(BufferError::SizeMismatch);
+    }
+    for (y, out) in dst.rows_mut().enumerate() {
+        for (x, cell) in out.iter_mut().enumerate() {
+            let n = src
+                .neighbours(x, y, Neighbourhood::Moore, edge)
+                .filter(|n| counts(&n.value))
+                .count();
+            *cell = next(src.row(y)[x], n);
+        }
+    }
+    Ok(())
+}
+
+/// Electrons running along wires: heads become tails, tails become conductor, and conductor
+/// next to one or two heads becomes a head.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wireworld {
+    pub edge: Edge<u8>,
+}
+
+impl Wireworld {
+    pub const EMPTY: u8 = 0;
+    pub const HEAD: u8 = 1;
+    pub const TAIL: u8 = 2;
+    pub const CONDUCTOR: u8 = 3;
+
+    /// Reads a circuit drawn with ` ` or `.` for empty, `H` head, `t` tail and `#` conductor.
+    pub fn parse_state(c: char) -> Option<u8> {
+        match c {
+            ' ' | '.' => Some(Self::EMPTY),
+            'H' => Some(Self::HEAD),
+            't' => Some(Self::TAIL),
+            '#' => Some(Self::CONDUCTOR),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Wireworld {
+    /// Circuits rarely touch the border, so nothing is outside.
+    fn default() -> Self {
+        Self {
+            edge: Edge::Constant(Self::EMPTY),
+        }
+    }
+}
+
+impl Automaton<u8> for Wireworld {
+    fn step<S, D>(&self, src: &S, dst: &mut D) -> Result<(), BufferError>
+    where
+        S: TwoDeeRead<u8> + ?Sized,
+        D: TwoDeeBuffer<u8> + ?Sized,
+    {
+        step_by_count(
+            src,
+            dst,
+            self.edge,
+            |c| *c == Self::HEAD,
+            |cell, heads| match cell {
+                Self::HEAD => Self::TAIL,
+                Self::TAIL => Self::CONDUCTOR,
+                Self::CONDUCTOR if heads == 1 || heads == 2 => Self::HEAD,
+                other => other,
+            },
+        )
+    }
+
+    fn palette(&self) -> Palette {
+        Palette::new(vec![
+            pack_rgba(0, 0, 0, 255),
+            pack_rgba(64, 128, 255, 255),
+            pack_rgba(255, 64, 32, 255),
+            pack_rgba(255, 192, 0, 255),
+        ])
+    }
+}
+
+/// Life-like rules where dying cells linger through extra "refractory" states before they
+/// die, e.g. Brian's Brain, `B2/S/C3`. State 0 is dead, 1 alive, and 2 up to `states - 1`
+/// dying. Only live cells count as neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Generations {
+    pub rule: LifeRule,
+    /// Total number of states, at least 2. With 2 this is plain Life-like.
+    pub states: u8,
+    pub edge: Edge<u8>,
+}
+
+impl Generations {
+    /// B2/S/C3
+    pub const BRIANS_BRAIN: Self = Self::new(LifeRule::new(1 << 2, 0), 3);
+    /// B2/S345/C4
+    pub const STAR_WARS: Self = Self::new(LifeRule::new(1 << 2, 0b111000), 4);
+
+    /// `rule` with `states` states on a wrapping world.
+    pub const fn new(rule: LifeRule, states: u8) -> Self {
+        Self {
+            rule,
+            states,
+            edge: Edge::Wrap,
+        }
+    }
+
+    pub fn with_edge(mut self, edge: Edge<u8>) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Reads `B2/S/C3` (the `C` is optional), or Golly's survival-first `/2/3`.
+    pub fn parse(rule: &str) -> Result<Self, RuleError> {
+        let malformed = || RuleError::Malformed(rule.to_string());
+        let (life, states) = rule.trim().rsplit_once('/').ok_or_else(malformed)?;
+        let states = states.trim_start_matches(['C', 'c', 'G', 'g']);
+        let states: u8 = states.parse().map_err(|_| malformed())?;
+        if states < 2 {
+            return Err(malformed());
+        }
+        let rule = LifeRule::parse(life).map_err(|e| match e {
+            RuleError::Malformed(_) => malformed(),
+            other => other,
+        })?;
+        Ok(Self::new(rule, states))
+    }
+}
+
+impl FromStr for Generations {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Generations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/C{}", self.rule, self.states)
+    }
+}
+
+impl Automaton<u8> for Generations {
+    fn step<S, D>(&self, src: &S, dst: &mut D) -> Result<(), BufferError>
+    where
+        S: TwoDeeRead<u8> + ?Sized,
+        D: TwoDeeBuffer<u8> + ?Sized,
+    {
+        step_by_count(
+            src,
+            dst,
+            self.edge,
+            |c| *c == 1,
+            |cell, live| match cell {
+                0 => self.rule.born(live) as u8,
+                1 if self.rule.survives(live) => 1,
+                // Live cells that don't survive start dying, dying cells carry on.
+                _ => cell.saturating_add(1) % self.states,
+            },
+        )
+    }
+
+    /// Dead black, alive white, and dying fading from red towards black.
+    fn palette(&self) -> Palette {
+        let dying = self.states.saturating_sub(2) as usize;
+        let mut colours = vec![pack_rgba(0, 0, 0, 255), pack_rgba(255, 255, 255, 255)];
+        colours.extend((0..dying).map(|i| {
+            let t = i as f32 / dying as f32;
+            lerp_rgba(pack_rgba(255, 48, 32, 255), pack_rgba(0, 0, 0, 255), t)
+        }));
+        Palette::new(colours)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufferHandle, Grid, ToText};
+
+    #[test]
+    fn electron_goes_round_a_loop() {
+        let circuit = Grid::try_parse(".tH.\n#..#\n.##.", Wireworld::parse_state).unwrap();
+        let mut buf = BufferHandle::with_value(4, 3, 0u8);
+        buf.update(|f| f.copy_from(&circuit).unwrap());
+        let wires = Wireworld::default();
+        let show = |g: &Grid<u8>| g.to_text(|c| ".Ht#".chars().nth(c as usize).unwrap());
+
+        wires.tick(&mut buf);
+        buf.render(|f| assert_eq!(show(f), ".#t.\n#..H\n.##.\n"));
+        // The loop is 6 cells long.
+        for _ in 0..5 {
+            wires.tick(&mut buf);
+        }
+        buf.render(|f| assert_eq!(f, &circuit));
+    }
+
+    #[test]
+    fn generations_rules() {
+        assert_eq!("B2/S/C3".parse(), Ok(Generations::BRIANS_BRAIN));
+        assert_eq!(Generations::parse("/2/3"), Ok(Generations::BRIANS_BRAIN));
+        assert_eq!(Generations::parse("345/2/4"), Ok(Generations::STAR_WARS));
+        assert_eq!(Generations::STAR_WARS.to_string(), "B2/S345/C4");
+        assert!(Generations::parse("B2/S/C1").is_err());
+        assert_eq!(Generations::parse("B2/S9/3"), Err(RuleError::BadCount('9')));
+        assert_eq!(Generations::BRIANS_BRAIN.palette().colours.len(), 3);
+    }
+
+    #[test]
+    fn brians_brain_cycles_states() {
+        // Two live cells side by side: neither survives, and the four cells touching both
+        // of them are born.
+        let start = Grid::parse("....\n.11.\n....", |c| c.to_digit(10).unwrap_or(0) as u8).unwrap();
+        let brain = Generations::BRIANS_BRAIN.with_edge(Edge::Skip);
+        let mut next = start.clone();
+        brain.step(&start, &mut next).unwrap();
+        assert_eq!(next.to_text(|c| char::from(b'0' + c)), "0110\n0220\n0110\n");
+        let mut after = start.clone();
+        brain.step(&next, &mut after).unwrap();
+        assert_eq!(after.get(1, 1), Ok(0));
+    }
+}
@@ -1,177 +1,578 @@
-use num::{Num, ToPrimitive};
+use crate::neighbours::{Edge, Neighbour, Neighbourhood};
+use crate::rect::Rect;
+use crate::view::{View, ViewMut};
+use bytemuck::Pod;
+use num::ToPrimitive;
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicPtr, Ordering};
-use std::sync::Arc;
-use std::usize;
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BufferError {
     #[error("Index out of bounds")]
     OutOfBounds,
     #[error("Bad index passed")]
     BadIndex,
+    #[error("Buffer length does not match dimensions")]
+    SizeMismatch,
+    #[error("Line {line} is {found} cells wide, expected {expected}")]
+    RaggedLine {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// `line` and `column` count from 1, in the text as given.
+    #[error("Unexpected {ch:?} at line {line}, column {column}")]
+    UnexpectedChar {
+        ch: char,
+        line: usize,
+        column: usize,
+    },
 }
 
-/// Provides simple x,y indexing into a buffer.
-pub struct SimpleTwoDeeBuffer {
+/// Runtime-sized grid, for when the dimensions aren't known until after parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<C: Copy> {
     width: usize,
     height: usize,
-    buf: Vec<u8>,
+    buf: Vec<C>,
 }
 
-impl SimpleTwoDeeBuffer {
-    fn new(width: usize, height: usize) -> Self {
-        let buf = vec![0u8; width * height];
+/// The original byte grid, kept around as a plain `Grid`.
+pub type SimpleTwoDeeBuffer = Grid<u8>;
+
+impl<C: Copy> Grid<C> {
+    pub fn new(width: usize, height: usize, initial: C) -> Self {
+        let buf = vec![initial; width * height];
         Self { width, height, buf }
     }
+
+    /// Wraps existing row-major cells, which must be exactly `width * height` long.
+    pub fn from_vec(width: usize, height: usize, buf: Vec<C>) -> Result<Self, BufferError> {
+        if buf.len() != width * height {
+            return Err(BufferError::SizeMismatch);
+        }
+        Ok(Self { width, height, buf })
+    }
+
+    // Also on `TwoDeeRead`, kept here so callers needn't import the trait.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Row-major cells.
+    pub fn cells(&self) -> &[C] {
+        &self.buf
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [C] {
+        &mut self.buf
+    }
+
+    pub fn into_vec(self) -> Vec<C> {
+        self.buf
+    }
+
+    /// Read-only window onto `rect`, in local coordinates.
+    pub fn view(&self, rect: Rect) -> Result<View<'_, C>, BufferError> {
+        View::new(&self.buf, self.width, rect)
+    }
+
+    /// Mutable window onto `rect`, in local coordinates.
+    pub fn view_mut(&mut self, rect: Rect) -> Result<ViewMut<'_, C>, BufferError> {
+        ViewMut::new(&mut self.buf, self.width, rect)
+    }
+
+    /// Splits the grid into disjoint mutable tiles, see `ViewMut::tiles`.
+    pub fn tiles_mut(&mut self, tile_width: usize, tile_height: usize) -> Vec<ViewMut<'_, C>> {
+        let rect = Rect::of_size(self.width, self.height);
+        ViewMut::new(&mut self.buf, self.width, rect)
+            .map(|v| v.tiles(tile_width, tile_height))
+            .unwrap_or_default()
+    }
 }
 
-pub trait TwoDeeBuffer<B: Num + Copy> {
-    fn get<T: ToPrimitive>(&self, x: T, y: T) -> Result<B, BufferError>;
-    fn set<T: ToPrimitive>(&mut self, x: T, y: T, v: B) -> Result<(), BufferError>;
+impl<C: Pod> Grid<C> {
+    /// Raw view of the cells, for uploading to the GPU.
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.buf)
+    }
 }
 
-impl TwoDeeBuffer<u8> for SimpleTwoDeeBuffer {
-    /// Safely retrieves a value from the buffer, for the given x,y coords.
-    fn get<T: ToPrimitive>(&self, x: T, y: T) -> Result<u8, BufferError> {
+/// The read side of a grid. Everything that can be looked at implements this, including
+/// read-only `View`s; `TwoDeeBuffer` adds the writes.
+pub trait TwoDeeRead<B: Copy> {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// Converts x,y to usize and checks each against its own axis, so (width, 0) is out of
+    /// bounds rather than spilling into the next row.
+    fn check_coords<T: ToPrimitive>(&self, x: T, y: T) -> Result<(usize, usize), BufferError> {
         let x = x.to_usize().ok_or(BufferError::BadIndex)?;
         let y = y.to_usize().ok_or(BufferError::BadIndex)?;
-        let i = y * self.width + x;
+        if x >= self.width() || y >= self.height() {
+            return Err(BufferError::OutOfBounds);
+        }
+        Ok((x, y))
+    }
+
+    /// Safely retrieves a value from the buffer, for the given x,y coords.
+    fn get<T: ToPrimitive>(&self, x: T, y: T) -> Result<B, BufferError> {
+        let (x, y) = self.check_coords(x, y)?;
+        Ok(self.row(y)[x])
+    }
 
-        self.buf.get(i).copied().ok_or(BufferError::OutOfBounds)
+    /// Retrieves a value without any bounds checks, for hot loops.
+    ///
+    /// # Safety
+    /// `x` must be less than `width()` and `y` less than `height()`.
+    unsafe fn get_unchecked(&self, x: usize, y: usize) -> B {
+        *self.row(y).get_unchecked(x)
     }
 
+    /// Cells of row `y`, left to right. Panics if `y` is out of range.
+    fn row(&self, y: usize) -> &[B];
+
+    /// Every row, top to bottom.
+    fn rows<'a>(&'a self) -> impl Iterator<Item = &'a [B]>
+    where
+        B: 'a,
+    {
+        (0..self.height()).map(|y| self.row(y))
+    }
+
+    /// Cells of column `x`, top to bottom. Panics if `x` is out of range.
+    fn column<'a>(&'a self, x: usize) -> impl Iterator<Item = &'a B>
+    where
+        B: 'a,
+    {
+        assert!(x < self.width(), "column {x} out of range");
+        self.rows().map(move |row| &row[x])
+    }
+
+    /// Every cell in row-major order, alongside its (x, y).
+    fn iter_coords<'a>(&'a self) -> impl Iterator<Item = ((usize, usize), &'a B)>
+    where
+        B: 'a,
+    {
+        self.rows()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, c)| ((x, y), c)))
+    }
+
+    /// Neighbours of (x, y) in `shape`, with out of range cells handled by `edge`.
+    fn neighbours<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        shape: Neighbourhood<'a>,
+        edge: Edge<B>,
+    ) -> impl Iterator<Item = Neighbour<B>> + 'a
+    where
+        B: 'a,
+    {
+        let (w, h) = (self.width(), self.height());
+        shape.offsets().filter_map(move |offset| {
+            let (nx, ny) = (x as isize + offset.0, y as isize + offset.1);
+            match edge.resolve(nx, ny, w, h) {
+                Some((px, py)) => Some(Neighbour {
+                    offset,
+                    pos: Some((px, py)),
+                    value: self.row(py)[px],
+                }),
+                None => match edge {
+                    Edge::Constant(value) => Some(Neighbour {
+                        offset,
+                        pos: None,
+                        value,
+                    }),
+                    _ => None,
+                },
+            }
+        })
+    }
+}
+
+pub trait TwoDeeBuffer<B: Copy>: TwoDeeRead<B> {
     /// Safely sets a value to the buffer, for the given x,y coords.
-    fn set<T: ToPrimitive>(&mut self, x: T, y: T, v: u8) -> Result<(), BufferError> {
-        let x = x.to_usize().ok_or(BufferError::BadIndex)?;
-        let y = y.to_usize().ok_or(BufferError::BadIndex)?;
-        let i = y * self.width + x;
+    fn set<T: ToPrimitive>(&mut self, x: T, y: T, v: B) -> Result<(), BufferError> {
+        let (x, y) = self.check_coords(x, y)?;
+        self.row_mut(y)[x] = v;
+        Ok(())
+    }
 
-        if i > self.width * self.height {
-            return Err(BufferError::OutOfBounds);
+    /// Sets a value without any bounds checks, for hot loops.
+    ///
+    /// # Safety
+    /// `x` must be less than `width()` and `y` less than `height()`.
+    unsafe fn set_unchecked(&mut self, x: usize, y: usize, v: B) {
+        *self.row_mut(y).get_unchecked_mut(x) = v;
+    }
+
+    /// Cells of row `y`, left to right, mutably. Panics if `y` is out of range.
+    fn row_mut(&mut self, y: usize) -> &mut [B];
+
+    /// Every row, top to bottom, mutably.
+    fn rows_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut [B]>
+    where
+        B: 'a;
+
+    /// Every cell in row-major order, mutably, alongside its (x, y).
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = ((usize, usize), &'a mut B)>
+    where
+        B: 'a,
+    {
+        self.rows_mut()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter_mut().enumerate().map(move |(x, c)| ((x, y), c)))
+    }
+
+    /// Sets every cell to `v`.
+    fn fill(&mut self, v: B) {
+        for row in self.rows_mut() {
+            row.fill(v);
         }
+    }
 
-        self.buf[i] = v;
+    /// Sets every cell inside `rect` to `v`, clipping it to the grid.
+    fn fill_rect(&mut self, rect: Rect, v: B) {
+        let rect = rect.intersect(&Rect::of_size(self.width(), self.height()));
+        if rect.is_empty() {
+            return;
+        }
+        for y in rect.y..rect.bottom() {
+            self.row_mut(y)[rect.x..rect.right()].fill(v);
+        }
+    }
 
+    /// Overwrites this grid with the contents of another of the same size.
+    fn copy_from<S: TwoDeeRead<B> + ?Sized>(&mut self, src: &S) -> Result<(), BufferError> {
+        if (src.width(), src.height()) != (self.width(), self.height()) {
+            return Err(BufferError::SizeMismatch);
+        }
+        for (dst, src) in self.rows_mut().zip(src.rows()) {
+            dst.copy_from_slice(src);
+        }
         Ok(())
     }
+
+    /// Copies `src_rect` out of `src` so its top left lands on `dst`. Anything falling outside
+    /// either grid is clipped. Returns the rect that was written to, in our coordinates, which is
+    /// empty if nothing was.
+    fn blit<S: TwoDeeRead<B> + ?Sized>(
+        &mut self,
+        src: &S,
+        src_rect: Rect,
+        dst: (usize, usize),
+    ) -> Rect {
+        let src_rect = src_rect.intersect(&Rect::of_size(src.width(), src.height()));
+        let dst_rect = Rect::new(dst.0, dst.1, src_rect.width, src_rect.height)
+            .intersect(&Rect::of_size(self.width(), self.height()));
+        if dst_rect.is_empty() {
+            return dst_rect;
+        }
+        for dy in 0..dst_rect.height {
+            let from = &src.row(src_rect.y + dy)[src_rect.x..src_rect.x + dst_rect.width];
+            self.row_mut(dst_rect.y + dy)[dst_rect.x..dst_rect.right()].copy_from_slice(from);
+        }
+        dst_rect
+    }
+}
+
+impl<C: Copy> TwoDeeRead<C> for Grid<C> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[C] {
+        &self.buf[y * self.width..(y + 1) * self.width]
+    }
+
+    unsafe fn get_unchecked(&self, x: usize, y: usize) -> C {
+        *self.buf.get_unchecked(y * self.width + x)
+    }
+}
+
+impl<C: Copy> TwoDeeBuffer<C> for Grid<C> {
+    fn row_mut(&mut self, y: usize) -> &mut [C] {
+        &mut self.buf[y * self.width..(y + 1) * self.width]
+    }
+
+    fn rows_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut [C]>
+    where
+        C: 'a,
+    {
+        rows_of(&mut self.buf, self.width, self.height)
+    }
+
+    unsafe fn set_unchecked(&mut self, x: usize, y: usize, v: C) {
+        *self.buf.get_unchecked_mut(y * self.width + x) = v;
+    }
 }
 
-/// Double buffer implementation.
+/// Splits row-major `cells` into `height` rows, including when they're zero wide and so
+/// empty, to match `TwoDeeRead::rows`.
+pub(crate) fn rows_of<C>(
+    cells: &mut [C],
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = &mut [C]> {
+    let empty_rows = if width == 0 { height } else { 0 };
+    cells
+        .chunks_exact_mut(width.max(1))
+        .chain(std::iter::repeat_with(|| -> &mut [C] { &mut [] }).take(empty_rows))
+}
+
+impl<C: Copy> Index<(usize, usize)> for Grid<C> {
+    type Output = C;
+
+    fn index(&self, (x, y): (usize, usize)) -> &C {
+        &self.row(y)[x]
+    }
+}
+
+impl<C: Copy> IndexMut<(usize, usize)> for Grid<C> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut C {
+        &mut self.row_mut(y)[x]
+    }
+}
+
+/// Double buffer implementation. Any number of readers share the front buffer while one
+/// writer at a time fills the back, and then the two swap.
 pub struct Flipper<F, T>
 where
-    T: Num + Copy,
+    T: Copy,
     F: TwoDeeBuffer<T>,
 {
-    a: Box<F>,
-    b: Box<F>,
-    active: AtomicPtr<F>,
+    buffers: [RwLock<F>; 2],
+    /// Index of the front buffer in `buffers`.
+    active: AtomicUsize,
+    /// Held for a whole step, so clones of a handle take turns writing.
+    writer: Mutex<()>,
     _marker: PhantomData<T>,
 }
 
 impl<F, T> Flipper<F, T>
 where
-    T: Num + Copy,
+    T: Copy,
     F: TwoDeeBuffer<T>,
 {
     /// Internally creates 2x TwoDeeBuffers.
     pub fn new(a: F, b: F) -> Self {
-        let mut a = Box::new(a);
-        let b = Box::new(b);
-        let active = AtomicPtr::new(a.as_mut());
-        let _marker = PhantomData::default();
-
         Self {
-            a,
-            b,
-            active,
-            _marker,
+            buffers: [RwLock::new(a), RwLock::new(b)],
+            active: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+            _marker: PhantomData,
         }
     }
 
     /// Flip front and back, to be called after every processing step.
     /// Not to be called directly, but in the wrapper one level up.
     pub fn flip(&mut self) {
-        let current = self.active.load(Ordering::Relaxed);
-        if current == self.a.as_mut() {
-            self.active.store(self.b.as_mut(), Ordering::Release);
-        } else {
-            self.active.store(self.a.as_mut(), Ordering::Release);
-        }
+        *self.active.get_mut() ^= 1;
     }
 
-    /// Buffer that is safe to read from. Use this for rendering.
-    pub fn front(&self) -> &F {
-        let ptr = self.active.load(Ordering::Acquire);
-        // SAFETY: Enforced by the wrapper one level up, after every mutation the buffer is flipped,
-        // therefor we can assure nothing is writing to this, and thus is safe to read from.
-        unsafe { &*ptr }
+    /// Buffer that is safe to read from. Use this for rendering. A writer waits for the
+    /// guard to be dropped before reusing the buffer as its back buffer.
+    pub fn front(&self) -> RwLockReadGuard<'_, F> {
+        let active = self.active.load(Ordering::Acquire);
+        self.buffers[active]
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
     }
 
     /// Buffer we process on. Used for updates.
     pub fn back(&mut self) -> &mut F {
-        let active_ptr = self.active.load(Ordering::Relaxed);
-        if active_ptr == self.a.as_mut() {
-            &mut self.b
-        } else {
-            &mut self.a
+        let back = *self.active.get_mut() ^ 1;
+        self.buffers[back]
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hands `step_func` the front buffer to read and the back buffer to write, then flips.
+    /// Backs `update` and `step` on the shared handles. Waits for other writers to finish,
+    /// and for readers still holding the back buffer from before the last flip.
+    pub(crate) fn step_shared<S: FnOnce(&F, &mut F)>(&self, step_func: S) {
+        let _writing = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let active = self.active.load(Ordering::Acquire);
+        {
+            let front = self.buffers[active]
+                .read()
+                .unwrap_or_else(|e| e.into_inner());
+            let mut back = self.buffers[active ^ 1]
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
+            step_func(&front, &mut back);
         }
+        self.active.store(active ^ 1, Ordering::Release);
     }
 }
 
 /// Thread-safe handle to the double buffer
 #[derive(Clone)]
-pub struct BufferHandle(Arc<Flipper<SimpleTwoDeeBuffer, u8>>);
+pub struct BufferHandle<C: Copy = u8>(Arc<Flipper<Grid<C>, C>>);
 
-impl BufferHandle {
-    pub fn new(width: usize, height: usize) -> Self {
-        let a = SimpleTwoDeeBuffer::new(width, height);
-        let b = SimpleTwoDeeBuffer::new(width, height);
+impl<C: Copy> BufferHandle<C> {
+    /// Both buffers start out filled with `initial`.
+    pub fn with_value(width: usize, height: usize, initial: C) -> Self {
+        let a = Grid::new(width, height, initial);
+        let b = Grid::new(width, height, initial);
         Self(Arc::new(Flipper::new(a, b)))
     }
 
+    pub fn width(&self) -> usize {
+        self.0.front().width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.front().height()
+    }
+
     // Uses the front buffer, which is safe for read-only access.
-    pub fn render<F: Fn(&SimpleTwoDeeBuffer)>(&self, render_func: F) {
-        let f = self.0.front();
-        render_func(f);
+    pub fn render<F: FnOnce(&Grid<C>)>(&self, render_func: F) {
+        render_func(&self.0.front());
     }
 
     // Uses the back buffer, which is not read from.
-    pub fn update<F: Fn(&mut SimpleTwoDeeBuffer)>(&mut self, update_func: F) {
-        let x = self.0.clone();
-        // SAFETY: Operations only ever occur on the back buffer. Buffers are swapped via
-        // an atomic pointer, via flip.
-        unsafe {
-            let ptr = Arc::into_raw(x) as *mut Flipper<SimpleTwoDeeBuffer, u8>;
-            let buf = (*ptr).back();
-            update_func(buf);
-            (*ptr).flip();
-        }
+    pub fn update<F: FnOnce(&mut Grid<C>)>(&mut self, update_func: F) {
+        self.step(|_, back| update_func(back));
+    }
+
+    /// Reads the front buffer while writing the back one, then flips. For sims that work out
+    /// each frame from the last.
+    pub fn step<F: FnOnce(&Grid<C>, &mut Grid<C>)>(&mut self, step_func: F) {
+        self.0.step_shared(step_func);
+    }
+}
+
+/// A double buffer a sim can publish frames through, so sims can be written once for both
+/// the fixed and runtime sized handles.
+pub trait Publisher<C: Copy> {
+    type Frame: TwoDeeBuffer<C>;
+
+    /// Writes into the back buffer, then publishes it.
+    fn publish<F: FnOnce(&mut Self::Frame)>(&mut self, f: F);
+
+    /// Reads the current frame while writing the next into the back buffer, then publishes it.
+    fn step<F: FnOnce(&Self::Frame, &mut Self::Frame)>(&mut self, f: F);
+
+    /// Reads the last published frame.
+    fn render<F: FnOnce(&Self::Frame)>(&self, f: F);
+}
+
+impl<C: Copy> Publisher<C> for BufferHandle<C> {
+    type Frame = Grid<C>;
+
+    fn publish<F: FnOnce(&mut Self::Frame)>(&mut self, f: F) {
+        self.update(f);
+    }
+
+    fn step<F: FnOnce(&Self::Frame, &mut Self::Frame)>(&mut self, f: F) {
+        BufferHandle::step(self, f);
+    }
+
+    fn render<F: FnOnce(&Self::Frame)>(&self, f: F) {
+        BufferHandle::render(self, f);
+    }
+}
+
+impl<C: Copy + Default> BufferHandle<C> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_value(width, height, C::default())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{FixedTwoDeeBuffer, Toroidal};
     use rand::prelude::*;
+    use std::collections::HashMap;
     use std::thread;
 
     #[test]
     fn test_buffer_out_of_bounds() {
-        let buffer = SimpleTwoDeeBuffer::new(5, 5);
+        let buffer = SimpleTwoDeeBuffer::new(5, 5, 0);
         assert!(matches!(buffer.get(5, 5), Err(BufferError::OutOfBounds)));
     }
 
     #[test]
     fn test_buffer_bad_index() {
-        let buffer = SimpleTwoDeeBuffer::new(5, 5);
+        let buffer = SimpleTwoDeeBuffer::new(5, 5, 0);
         assert!(matches!(buffer.get(-1, 0), Err(BufferError::BadIndex)));
     }
 
+    #[test]
+    fn grid_from_parsed_dimensions() {
+        let grid = Grid::from_vec(3, 2, vec!['#', '.', '#', '.', '.', 'E']).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(2, 1).unwrap(), 'E');
+        assert!(matches!(
+            Grid::from_vec(3, 3, vec![0u8; 4]),
+            Err(BufferError::SizeMismatch)
+        ));
+    }
+
+    #[test]
+    fn rows_columns_and_coords() {
+        let mut grid = Grid::from_vec(3, 2, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(grid.row(1), &[4, 5, 6]);
+        assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), vec![3, 6]);
+        assert_eq!(grid.rows().count(), 2);
+        assert_eq!(grid[(0, 1)], 4);
+
+        for ((x, y), c) in grid.iter_mut() {
+            *c = x * 10 + y;
+        }
+        grid.row_mut(0)[0] = 99;
+        let coords: Vec<_> = grid.iter_coords().map(|(p, c)| (p, *c)).collect();
+        assert_eq!(coords[0], ((0, 0), 99));
+        assert_eq!(coords[5], ((2, 1), 21));
+    }
+
+    #[test]
+    fn rows_agree_on_empty_grids() {
+        for (w, h) in [(0, 3), (3, 0), (0, 0)] {
+            let mut grid = Grid::new(w, h, 0u8);
+            assert_eq!(grid.rows_mut().count(), grid.rows().count(), "{w}x{h}");
+            assert_eq!(grid.rows().count(), h);
+        }
+        let mut fixed = FixedTwoDeeBuffer::<u8, 0, 4>::new(0);
+        assert_eq!(fixed.rows_mut().count(), 4);
+        assert_eq!((fixed.width(), fixed.height()), (0, 4));
+    }
+
+    #[test]
+    fn test_buffer_axes_checked_independently() {
+        let mut buffer = Grid::new(5, 3, 0u8);
+        assert_eq!(buffer.set(5, 0, 1), Err(BufferError::OutOfBounds));
+        assert_eq!(buffer.set(0, 3, 1), Err(BufferError::OutOfBounds));
+        assert_eq!(buffer.get(1, 1), Ok(0));
+    }
+
     #[test]
     fn can_share() {
-        let buf = BufferHandle::new(100, 100);
+        let buf = BufferHandle::<u8>::new(100, 100);
 
         let r_buf = buf.clone();
         let renderer = thread::spawn(move || {
@@ -203,9 +604,20 @@ mod tests {
         renderer.join().unwrap();
     }
 
+    #[test]
+    fn publishing_keeps_no_extra_handles() {
+        let mut grid = BufferHandle::<u8>::new(2, 2);
+        for _ in 0..3 {
+            grid.update(|f| f.fill(1));
+            grid.step(|front, back| back.copy_from(front).unwrap());
+        }
+        assert_eq!(Arc::strong_count(&grid.0), 1);
+        grid.render(|f| assert_eq!(f.get(1, 1), Ok(1)));
+    }
+
     #[test]
     fn test_concurrent_access() {
-        let buf = BufferHandle::new(100, 100);
+        let buf = BufferHandle::<u8>::new(100, 100);
 
         // Spin up multiple render threads
         let mut renderers = vec![];
@@ -250,4 +662,128 @@ mod tests {
             worker.join().unwrap();
         }
     }
+
+    /// Reference model the grid types are checked against: a sparse map with the
+    /// documented error rules spelled out longhand.
+    struct Model {
+        width: usize,
+        height: usize,
+        wrap: bool,
+        cells: HashMap<(usize, usize), u32>,
+    }
+
+    impl Model {
+        fn resolve(&self, x: i64, y: i64) -> Result<(usize, usize), BufferError> {
+            if self.wrap {
+                if self.width == 0 || self.height == 0 {
+                    return Err(BufferError::OutOfBounds);
+                }
+                let x = x.rem_euclid(self.width as i64) as usize;
+                let y = y.rem_euclid(self.height as i64) as usize;
+                return Ok((x, y));
+            }
+            if x < 0 || y < 0 {
+                return Err(BufferError::BadIndex);
+            }
+            if x as usize >= self.width || y as usize >= self.height {
+                return Err(BufferError::OutOfBounds);
+            }
+            Ok((x as usize, y as usize))
+        }
+
+        fn get(&self, x: i64, y: i64) -> Result<u32, BufferError> {
+            let p = self.resolve(x, y)?;
+            Ok(self.cells.get(&p).copied().unwrap_or(0))
+        }
+
+        fn set(&mut self, x: i64, y: i64, v: u32) -> Result<(), BufferError> {
+            let p = self.resolve(x, y)?;
+            self.cells.insert(p, v);
+            Ok(())
+        }
+    }
+
+    /// Writes every coordinate in and around the grid, then random ops, checking each
+    /// result and the full contents against the model.
+    fn check_against_model<B: TwoDeeBuffer<u32>>(buf: &mut B, wrap: bool, seed: u64) {
+        let (w, h) = (buf.width(), buf.height());
+        let mut model = Model {
+            width: w,
+            height: h,
+            wrap,
+            cells: HashMap::new(),
+        };
+        let mut rng = StdRng::seed_from_u64(seed);
+        let span = |len: usize| -3..len as i64 + 3;
+
+        let mut v = 1;
+        for y in span(h) {
+            for x in span(w) {
+                assert_eq!(
+                    buf.set(x, y, v),
+                    model.set(x, y, v),
+                    "set ({x}, {y}) on {w}x{h}"
+                );
+                v += 1;
+            }
+        }
+        for _ in 0..500 {
+            let x = rng.gen_range(span(w));
+            let y = rng.gen_range(span(h));
+            if rng.gen() {
+                let v = rng.gen();
+                assert_eq!(
+                    buf.set(x, y, v),
+                    model.set(x, y, v),
+                    "set ({x}, {y}) on {w}x{h}"
+                );
+            } else {
+                assert_eq!(buf.get(x, y), model.get(x, y), "get ({x}, {y}) on {w}x{h}");
+            }
+        }
+
+        for y in span(h) {
+            for x in span(w) {
+                assert_eq!(buf.get(x, y), model.get(x, y), "get ({x}, {y}) on {w}x{h}");
+            }
+        }
+        for y in 0..h {
+            for x in 0..w {
+                let expected = model.get(x as i64, y as i64).unwrap();
+                // SAFETY: x and y are within the grid.
+                assert_eq!(unsafe { buf.get_unchecked(x, y) }, expected);
+                assert_eq!(buf.row(y)[x], expected);
+                unsafe { buf.set_unchecked(x, y, expected + 1) };
+                assert_eq!(buf.get(x, y), Ok(expected + 1));
+            }
+        }
+        // Non-integral and unrepresentable coords never index anything.
+        assert_eq!(buf.get(f64::NAN, 0.0), Err(BufferError::BadIndex));
+        assert_eq!(buf.get(0.0, f64::INFINITY), Err(BufferError::BadIndex));
+    }
+
+    macro_rules! check_fixed {
+        ($($w:literal x $h:literal),*) => {$(
+            check_against_model(&mut FixedTwoDeeBuffer::<u32, $w, $h>::new(0), false, $w * 31 + $h);
+            check_against_model(&mut Toroidal::new(FixedTwoDeeBuffer::<u32, $w, $h>::new(0)), true, $w * 17 + $h);
+        )*};
+    }
+
+    #[test]
+    fn model_runtime_grids() {
+        for w in 0..7 {
+            for h in 0..7 {
+                let seed = (w * 7 + h) as u64;
+                check_against_model(&mut Grid::new(w, h, 0), false, seed);
+                check_against_model(&mut Toroidal::new(Grid::new(w, h, 0)), true, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn model_fixed_grids() {
+        check_fixed!(1 x 1, 1 x 6, 6 x 1, 2 x 5, 5 x 2, 4 x 4, 7 x 3, 3 x 7);
+        check_against_model(&mut FixedTwoDeeBuffer::<u32, 0, 0>::new(0), false, 0);
+        check_against_model(&mut FixedTwoDeeBuffer::<u32, 0, 4>::new(0), false, 0);
+    }
 }
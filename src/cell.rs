@@ -0,0 +1,32 @@
+/// Cells that are either alive or dead, so two-state patterns and rules can work on
+/// whatever type a grid happens to store.
+pub trait BinaryCell: Copy {
+    fn is_alive(&self) -> bool;
+    fn from_alive(alive: bool) -> Self;
+}
+
+impl BinaryCell for bool {
+    fn is_alive(&self) -> bool {
+        *self
+    }
+
+    fn from_alive(alive: bool) -> Self {
+        alive
+    }
+}
+
+macro_rules! binary_ints {
+    ($($t:ty),*) => {$(
+        impl BinaryCell for $t {
+            fn is_alive(&self) -> bool {
+                *self != 0
+            }
+
+            fn from_alive(alive: bool) -> Self {
+                alive as $t
+            }
+        }
+    )*};
+}
+
+binary_ints!(u8, u16, u32, i32);
@@ -0,0 +1,135 @@
+use rand::prelude::*;
+
+use crate::pack_rgba;
+
+/// Splits a colour made by `pack_rgba` back into [r, g, b, a].
+pub fn unpack_rgba(c: u32) -> [u8; 4] {
+    c.to_le_bytes()
+}
+
+/// Linear blend between two packed colours, `t` in 0..=1.
+pub fn lerp_rgba(a: u32, b: u32, t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    let (a, b) = (unpack_rgba(a), unpack_rgba(b));
+    let mix = |i: usize| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8;
+    pack_rgba(mix(0), mix(1), mix(2), mix(3))
+}
+
+/// Maps scalar values onto colours, for showing non-colour grids.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Colormap {
+    /// Black to white.
+    Greyscale,
+    /// Black through red and yellow to white.
+    Heat,
+    /// Blue through white to red, for signed values.
+    Diverging,
+}
+
+impl Colormap {
+    const HEAT: [u32; 4] = [0xFF000000, 0xFF0000FF, 0xFF00FFFF, 0xFFFFFFFF];
+    const DIVERGING: [u32; 3] = [0xFFFF0000, 0xFFFFFFFF, 0xFF0000FF];
+
+    /// Colour for `t`, clamped to 0..=1.
+    pub fn map(&self, t: f32) -> u32 {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let stops: &[u32] = match self {
+            Colormap::Greyscale => return lerp_rgba(0xFF000000, 0xFFFFFFFF, t),
+            Colormap::Heat => &Self::HEAT,
+            Colormap::Diverging => &Self::DIVERGING,
+        };
+        let scaled = t * (stops.len() - 1) as f32;
+        let i = (scaled.floor() as usize).min(stops.len() - 2);
+        lerp_rgba(stops[i], stops[i + 1], scaled - i as f32)
+    }
+
+    /// Closure mapping `min..=max` across the colormap, for use with the image and render
+    /// helpers.
+    pub fn scaled<T: Into<f64>>(self, min: T, max: T) -> impl Fn(T) -> u32 {
+        let (min, max) = (min.into(), max.into());
+        let range = if max > min { max - min } else { 1.0 };
+        move |v| self.map(((v.into() - min) / range) as f32)
+    }
+}
+
+/// Fixed colours for small integer cell states, such as the states of a multi-state
+/// automaton. Use it with `FrameSource::coloured`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Palette {
+    pub colours: Vec<u32>,
+    /// Shown for states past the end of `colours`.
+    pub fallback: u32,
+}
+
+impl Palette {
+    /// Magenta, so missing states stand out.
+    pub const MISSING: u32 = 0xFFFF00FF;
+
+    pub fn new(colours: Vec<u32>) -> Self {
+        Self {
+            colours,
+            fallback: Self::MISSING,
+        }
+    }
+
+    /// `states` colours spread evenly across `colormap`.
+    pub fn from_colormap(colormap: Colormap, states: usize) -> Self {
+        let last = states.saturating_sub(1).max(1) as f32;
+        Self::new((0..states).map(|i| colormap.map(i as f32 / last)).collect())
+    }
+
+    /// `states` random bright-ish colours, the same ones for the same `seed`. Handy for
+    /// telling apart lots of labels.
+    pub fn random(states: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut channel = move || rng.gen_range(64..=255);
+        Self::new(
+            (0..states)
+                .map(|_| pack_rgba(channel(), channel(), channel(), 255))
+                .collect(),
+        )
+    }
+
+    /// Colour for `state`, or `fallback` for states past the end or below zero.
+    pub fn colour<S: TryInto<usize>>(&self, state: S) -> u32 {
+        state
+            .try_into()
+            .ok()
+            .and_then(|i| self.colours.get(i))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_packed() {
+        assert_eq!(unpack_rgba(pack_rgba(1, 2, 3, 4)), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn colormap_ends() {
+        assert_eq!(Colormap::Greyscale.map(0.0), pack_rgba(0, 0, 0, 255));
+        assert_eq!(Colormap::Heat.map(2.0), pack_rgba(255, 255, 255, 255));
+        assert_eq!(Colormap::Diverging.map(0.5), pack_rgba(255, 255, 255, 255));
+        let grey = Colormap::Greyscale.scaled(0.0f32, 10.0);
+        assert_eq!(grey(5.0), pack_rgba(128, 128, 128, 255));
+    }
+
+    #[test]
+    fn palette_lookup() {
+        let palette = Palette::from_colormap(Colormap::Greyscale, 3);
+        assert_eq!(palette.colour(0u8), pack_rgba(0, 0, 0, 255));
+        assert_eq!(palette.colour(true), pack_rgba(128, 128, 128, 255));
+        assert_eq!(palette.colour(3u8), Palette::MISSING);
+        assert_eq!(palette.colour(2u32), pack_rgba(255, 255, 255, 255));
+        assert_eq!(palette.colour(-1i32), Palette::MISSING);
+        assert_eq!(Palette::random(5, 9), Palette::random(5, 9));
+        assert_ne!(Palette::random(5, 9), Palette::random(5, 10));
+    }
+}
@@ -0,0 +1,260 @@
+//! Finding which cells changed between two grids, and a fading highlight of those changes for
+//! the viewer.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::buf::{BufferError, Grid, TwoDeeRead};
+use crate::colour::lerp_rgba;
+use crate::pack_rgba;
+use crate::rect::Rect;
+
+/// Cells that differ between two equally sized grids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridDiff {
+    width: usize,
+    height: usize,
+    changed: Vec<(usize, usize)>,
+}
+
+/// Compares `a` and `b` cell by cell. Both must be the same size.
+pub fn diff<C, A, B>(a: &A, b: &B) -> Result<GridDiff, BufferError>
+where
+    C: Copy + PartialEq,
+    A: TwoDeeRead<C> + ?Sized,
+    B: TwoDeeRead<C> + ?Sized,
+{
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return Err(BufferError::SizeMismatch);
+    }
+    let mut changed = vec![];
+    for (y, (ra, rb)) in a.rows().zip(b.rows()).enumerate() {
+        changed.extend(
+            ra.iter()
+                .zip(rb)
+                .enumerate()
+                .filter(|(_, (ca, cb))| ca != cb)
+                .map(|(x, _)| (x, y)),
+        );
+    }
+    Ok(GridDiff {
+        width: a.width(),
+        height: a.height(),
+        changed,
+    })
+}
+
+impl GridDiff {
+    /// Changed cells in row-major order.
+    pub fn changed(&self) -> &[(usize, usize)] {
+        &self.changed
+    }
+
+    pub fn count(&self) -> usize {
+        self.changed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+
+    /// Smallest rect holding every change, empty if nothing changed.
+    pub fn bounds(&self) -> Rect {
+        self.changed.iter().fold(Rect::default(), |acc, &(x, y)| {
+            acc.union(&Rect::new(x, y, 1, 1))
+        })
+    }
+
+    /// Covers the changes with rects: runs of changed cells along each row, merged downwards
+    /// while the run below spans exactly the same columns.
+    pub fn rects(&self) -> Vec<Rect> {
+        let mut rects: Vec<Rect> = vec![];
+        // Rects that reached the previous row, by (x, width).
+        let mut open: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut i = 0;
+        while i < self.changed.len() {
+            let (x, y) = self.changed[i];
+            let mut width = 1;
+            while self.changed.get(i + width) == Some(&(x + width, y)) {
+                width += 1;
+            }
+            i += width;
+
+            match open.get(&(x, width)) {
+                Some(&r) if rects[r].bottom() == y => rects[r].height += 1,
+                _ => {
+                    open.insert((x, width), rects.len());
+                    rects.push(Rect::new(x, y, width, 1));
+                }
+            }
+        }
+        rects
+    }
+
+    /// `true` where a cell changed.
+    pub fn mask(&self) -> Grid<bool> {
+        let mut mask = Grid::new(self.width, self.height, false);
+        for &(x, y) in &self.changed {
+            mask[(x, y)] = true;
+        }
+        mask
+    }
+}
+
+/// Tints cells of a packed colour frame that changed, fading the tint back out over `fade`.
+/// Changes are either given as the cells each published frame touched, or found by comparing
+/// against the previous frame drawn.
+#[derive(Clone, Debug)]
+pub struct DiffOverlay {
+    pub tint: u32,
+    pub fade: Duration,
+    previous: Vec<u32>,
+    heat: Vec<f32>,
+    out: Vec<u32>,
+}
+
+impl DiffOverlay {
+    /// How far a freshly changed cell is pulled towards the tint.
+    const STRENGTH: f32 = 0.7;
+
+    pub fn new(tint: u32, fade: Duration) -> Self {
+        Self {
+            tint,
+            fade,
+            previous: vec![],
+            heat: vec![],
+            out: vec![],
+        }
+    }
+
+    /// Forgets the previous frame, so nothing is highlighted on the next `apply`.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    /// Takes a frame of packed colours and the time since the last call, and returns the
+    /// frame with cells that differ from the previous one tinted.
+    pub fn apply(&mut self, frame: &[u32], elapsed: Duration) -> &[u32] {
+        self.cool(frame, elapsed);
+        for (i, &cell) in frame.iter().enumerate() {
+            if cell != self.previous[i] {
+                self.heat[i] = 1.0;
+            }
+        }
+        self.tint(frame)
+    }
+
+    /// Like `apply`, but tints the cells at the row-major indices in `changed`, such as those
+    /// from `FrameSource::changes_since`, even if they have since changed back.
+    pub fn apply_changes(&mut self, frame: &[u32], changed: &[usize], elapsed: Duration) -> &[u32] {
+        self.cool(frame, elapsed);
+        for &i in changed {
+            if let Some(heat) = self.heat.get_mut(i) {
+                *heat = 1.0;
+            }
+        }
+        self.tint(frame)
+    }
+
+    /// Fades every cell by `elapsed`, starting over if the frame size changed.
+    fn cool(&mut self, frame: &[u32], elapsed: Duration) {
+        if self.previous.len() != frame.len() {
+            self.previous = frame.to_vec();
+            self.heat = vec![0.0; frame.len()];
+            self.out = self.previous.clone();
+        }
+        let decay = if self.fade.is_zero() {
+            1.0
+        } else {
+            elapsed.as_secs_f32() / self.fade.as_secs_f32()
+        };
+        for heat in &mut self.heat {
+            *heat = (*heat - decay).max(0.0);
+        }
+    }
+
+    fn tint(&mut self, frame: &[u32]) -> &[u32] {
+        for (i, &cell) in frame.iter().enumerate() {
+            let heat = self.heat[i];
+            self.previous[i] = cell;
+            self.out[i] = if heat > 0.0 {
+                lerp_rgba(cell, self.tint, heat * Self::STRENGTH)
+            } else {
+                cell
+            };
+        }
+        &self.out
+    }
+}
+
+impl Default for DiffOverlay {
+    /// Magenta, fading over half a second.
+    fn default() -> Self {
+        Self::new(pack_rgba(255, 0, 255, 255), Duration::from_millis(500))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{unpack_rgba, FixedTwoDeeBuffer};
+
+    #[test]
+    fn finds_changes() {
+        let a = Grid::parse("....\n....\n....", |c| c).unwrap();
+        let b = Grid::parse(".##.\n.##.\n...#", |c| c).unwrap();
+        let d = diff(&a, &b).unwrap();
+        assert_eq!(d.count(), 5);
+        assert_eq!(d.changed()[0], (1, 0));
+        assert_eq!(d.bounds(), Rect::new(1, 0, 3, 3));
+        assert_eq!(
+            d.rects(),
+            vec![Rect::new(1, 0, 2, 2), Rect::new(3, 2, 1, 1)]
+        );
+        assert_eq!(d.mask().get(3, 2), Ok(true));
+
+        assert!(diff(&a, &a).unwrap().is_empty());
+        assert_eq!(diff(&a, &a).unwrap().bounds(), Rect::default());
+        let other = FixedTwoDeeBuffer::<char, 2, 2>::new('.');
+        assert_eq!(diff(&a, &other), Err(BufferError::SizeMismatch));
+    }
+
+    #[test]
+    fn overlay_fades() {
+        let black = pack_rgba(0, 0, 0, 255);
+        let white = pack_rgba(255, 255, 255, 255);
+        let mut overlay = DiffOverlay::new(white, Duration::from_millis(100));
+        let frame = |c: u32| vec![c, black];
+        let green = |c: u32| unpack_rgba(c)[1];
+
+        assert_eq!(overlay.apply(&frame(black), Duration::ZERO), frame(black));
+        let red = pack_rgba(255, 0, 0, 255);
+        let tinted = overlay.apply(&frame(red), Duration::ZERO).to_vec();
+        assert_ne!(tinted, frame(red));
+        assert_eq!(tinted[1], black);
+
+        let half = overlay
+            .apply(&frame(red), Duration::from_millis(50))
+            .to_vec();
+        assert!(green(half[0]) < green(tinted[0]));
+        let gone = overlay.apply(&frame(red), Duration::from_millis(60));
+        assert_eq!(gone, frame(red));
+    }
+
+    #[test]
+    fn overlay_takes_published_changes() {
+        let black = pack_rgba(0, 0, 0, 255);
+        let white = pack_rgba(255, 255, 255, 255);
+        let mut overlay = DiffOverlay::new(white, Duration::from_millis(100));
+        let frame = vec![black; 3];
+        overlay.apply_changes(&frame, &[], Duration::ZERO);
+
+        // Cell 1 flickered and went back between draws, so only the published changes show it.
+        let tinted = overlay
+            .apply_changes(&frame, &[1, 1, 7], Duration::ZERO)
+            .to_vec();
+        assert_eq!(tinted[0], black);
+        assert_ne!(tinted[1], black);
+        assert_eq!(overlay.apply(&frame, Duration::from_millis(100)), frame);
+    }
+}
@@ -0,0 +1,373 @@
+//! Heat spreading over `f32` grids: the heat equation `du/dt = a * laplacian(u)` on a grid of
+//! unit cells, with the front buffer as the current time and the back buffer as the next.
+
+use crate::buf::{BufferError, Grid, Publisher, TwoDeeBuffer, TwoDeeRead};
+use crate::neighbours::Edge;
+
+/// What's past the edges of the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Boundary {
+    /// Held at a fixed temperature, so heat leaks in or out.
+    Dirichlet(f32),
+    /// Insulated: no heat crosses the edge.
+    Neumann,
+    /// Wraps round to the opposite edge.
+    Periodic,
+}
+
+/// How each step is worked out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Scheme {
+    /// Forward Euler. Cheap, but only stable while `Diffusion::ratio` is at most 0.25.
+    Explicit,
+    /// Backward Euler, solved with this many Jacobi iterations. Stable for any time step.
+    Jacobi(usize),
+    /// Backward Euler, solved with this many Gauss-Seidel sweeps, updating cells in place.
+    /// Converges about twice as fast as Jacobi.
+    GaussSeidel(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeatSource {
+    /// Adds `rate` heat per unit time to (x, y).
+    Rate { x: usize, y: usize, rate: f32 },
+    /// Holds (x, y) at `value`.
+    Fixed { x: usize, y: usize, value: f32 },
+}
+
+/// A heat equation solver. Sources off the grid are ignored.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diffusion {
+    /// How fast heat spreads.
+    pub coefficient: f32,
+    /// Time per step.
+    pub dt: f32,
+    pub boundary: Boundary,
+    pub scheme: Scheme,
+    pub sources: Vec<HeatSource>,
+    /// Scratch reused by `step`: the current state plus what the `Rate` sources add, which
+    /// cells `Fixed` sources hold, and the previous Jacobi iterate.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    known: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    held: Vec<bool>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    prev: Vec<f32>,
+}
+
+/// Compares the settings, not the scratch space.
+impl PartialEq for Diffusion {
+    fn eq(&self, other: &Self) -> bool {
+        self.coefficient == other.coefficient
+            && self.dt == other.dt
+            && self.boundary == other.boundary
+            && self.scheme == other.scheme
+            && self.sources == other.sources
+    }
+}
+
+impl Diffusion {
+    /// Explicit steps with insulated edges and no sources.
+    pub fn new(coefficient: f32, dt: f32) -> Self {
+        Self {
+            coefficient,
+            dt,
+            boundary: Boundary::Neumann,
+            scheme: Scheme::Explicit,
+            sources: vec![],
+            known: vec![],
+            held: vec![],
+            prev: vec![],
+        }
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn with_source(mut self, source: HeatSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// `coefficient * dt`, how much of the difference from its neighbours a cell takes on
+    /// each step.
+    pub fn ratio(&self) -> f32 {
+        self.coefficient * self.dt
+    }
+
+    /// Whether steps stay bounded instead of blowing up into oscillations.
+    pub fn is_stable(&self) -> bool {
+        self.scheme != Scheme::Explicit || self.ratio() <= 0.25
+    }
+
+    /// Writes the state one step after `src` into `dst`, which must be the same size.
+    pub fn step<S, D>(&mut self, src: &S, dst: &mut D) -> Result<(), BufferError>
+    where
+        S: TwoDeeRead<f32> + ?Sized,
+        D: TwoDeeBuffer<f32> + ?Sized,
+    {
+        let (w, h) = (src.width(), src.height());
+        if (w, h) != (dst.width(), dst.height()) {
+            return Err(BufferError::SizeMismatch);
+        }
+        // The known side of the update: the current state plus whatever the sources add.
+        let mut known = std::mem::take(&mut self.known);
+        known.clear();
+        known.extend(src.rows().flatten());
+        let mut held = std::mem::take(&mut self.held);
+        held.clear();
+        held.resize(w * h, false);
+        for source in &self.sources {
+            match *source {
+                HeatSource::Rate { x, y, rate } if x < w && y < h => {
+                    known[y * w + x] += rate * self.dt;
+                }
+                HeatSource::Fixed { x, y, .. } if x < w && y < h => held[y * w + x] = true,
+                _ => {}
+            }
+        }
+
+        let r = self.ratio();
+        match self.scheme {
+            Scheme::Explicit => {
+                for (y, out) in dst.rows_mut().enumerate() {
+                    for (x, cell) in out.iter_mut().enumerate() {
+                        let here = src.row(y)[x];
+                        *cell = known[y * w + x] + r * (self.neighbour_sum(src, x, y) - 4.0 * here);
+                    }
+                }
+            }
+            Scheme::Jacobi(iterations) => {
+                // Solves (1 + 4r) u' - r * (neighbours of u') = known, starting from u.
+                dst.copy_from(src)?;
+                let mut prev = std::mem::take(&mut self.prev);
+                prev.resize(w * h, 0.0);
+                let mut prev = Grid::from_vec(w, h, prev)?;
+                // Held cells keep their value through every iteration, so their neighbours
+                // always see it.
+                self.hold_fixed(dst);
+                for _ in 0..iterations {
+                    prev.copy_from(dst)?;
+                    for (y, out) in dst.rows_mut().enumerate() {
+                        for (x, cell) in out.iter_mut().enumerate() {
+                            if held[y * w + x] {
+                                continue;
+                            }
+                            let sum = self.neighbour_sum(&prev, x, y);
+                            *cell = (known[y * w + x] + r * sum) / (1.0 + 4.0 * r);
+                        }
+                    }
+                }
+                self.prev = prev.into_vec();
+            }
+            Scheme::GaussSeidel(iterations) => {
+                dst.copy_from(src)?;
+                self.hold_fixed(dst);
+                for _ in 0..iterations {
+                    for y in 0..h {
+                        for x in 0..w {
+                            if held[y * w + x] {
+                                continue;
+                            }
+                            let sum = self.neighbour_sum(dst, x, y);
+                            dst.row_mut(y)[x] = (known[y * w + x] + r * sum) / (1.0 + 4.0 * r);
+                        }
+                    }
+                }
+            }
+        }
+        self.known = known;
+        self.held = held;
+        self.hold_fixed(dst);
+        Ok(())
+    }
+
+    /// Advances the published frame of `buf` by one step.
+    pub fn tick<P: Publisher<f32>>(&mut self, buf: &mut P) {
+        buf.step(|front, back| {
+            self.step(front, back)
+                .expect("front and back buffers are the same size")
+        });
+    }
+
+    fn hold_fixed<B: TwoDeeBuffer<f32> + ?Sized>(&self, grid: &mut B) {
+        for source in &self.sources {
+            if let HeatSource::Fixed { x, y, value } = *source {
+                let _ = grid.set(x, y, value);
+            }
+        }
+    }
+
+    /// The four edge neighbours of (x, y) added up, with the boundary filling in any that are
+    /// off the grid.
+    fn neighbour_sum<B: TwoDeeRead<f32> + ?Sized>(&self, grid: &B, x: usize, y: usize) -> f32 {
+        let (w, h) = (grid.width(), grid.height());
+        let here = grid.row(y)[x];
+        [(0, -1), (1, 0), (0, 1), (-1, 0)]
+            .into_iter()
+            .map(|(dx, dy)| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if let Some((nx, ny)) = Edge::<f32>::Skip.resolve(nx, ny, w, h) {
+                    return grid.row(ny)[nx];
+                }
+                match self.boundary {
+                    Boundary::Dirichlet(v) => v,
+                    // A ghost cell matching this one means nothing flows across.
+                    Boundary::Neumann => here,
+                    Boundary::Periodic => {
+                        let (nx, ny) = Edge::<f32>::Wrap.resolve(nx, ny, w, h).unwrap();
+                        grid.row(ny)[nx]
+                    }
+                }
+            })
+            .sum()
+    }
+}
+
+/// Sum of every cell, which insulated and periodic grids keep constant without sources.
+pub fn total_heat<B: TwoDeeRead<f32> + ?Sized>(grid: &B) -> f32 {
+    grid.rows().map(|row| row.iter().sum::<f32>()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DoubleBuf;
+
+    fn hot_spot() -> DoubleBuf<f32, 9, 9> {
+        let mut buf = DoubleBuf::new();
+        buf.update(|f| f.set(4, 4, 100.0).unwrap());
+        buf
+    }
+
+    #[test]
+    fn explicit_spreads_and_conserves() {
+        for boundary in [Boundary::Neumann, Boundary::Periodic] {
+            let mut heat = Diffusion::new(1.0, 0.2).with_boundary(boundary);
+            let mut buf = hot_spot();
+            for _ in 0..50 {
+                heat.tick(&mut buf);
+            }
+            buf.render(|f| {
+                assert!((total_heat(f) - 100.0).abs() < 1e-3, "{boundary:?}");
+                assert!(f.get(4, 4).unwrap() < 10.0);
+                let mirrored = |a: (usize, usize), b: (usize, usize)| {
+                    (f.get(a.0, a.1).unwrap() - f.get(b.0, b.1).unwrap()).abs() < 1e-5
+                };
+                assert!(mirrored((0, 4), (8, 4)) && mirrored((4, 0), (4, 8)));
+            });
+        }
+
+        // Cold edges soak the heat up.
+        let mut heat = Diffusion::new(1.0, 0.2).with_boundary(Boundary::Dirichlet(0.0));
+        let mut buf = hot_spot();
+        for _ in 0..50 {
+            heat.tick(&mut buf);
+        }
+        buf.render(|f| assert!(total_heat(f) < 50.0));
+    }
+
+    #[test]
+    fn implicit_schemes_stay_stable() {
+        // Far too big a step for the explicit scheme.
+        let mut explicit = Diffusion::new(1.0, 2.0);
+        assert!(!explicit.is_stable());
+        let mut buf = hot_spot();
+        for _ in 0..10 {
+            explicit.tick(&mut buf);
+        }
+        buf.render(|f| assert!(f.rows().flatten().any(|c| *c < 0.0)));
+
+        let mut results = vec![];
+        for scheme in [Scheme::Jacobi(200), Scheme::GaussSeidel(100)] {
+            let mut heat = explicit.clone().with_scheme(scheme);
+            assert!(heat.is_stable());
+            let mut buf = hot_spot();
+            for _ in 0..10 {
+                heat.tick(&mut buf);
+            }
+            buf.render(|f| {
+                assert!(f.rows().flatten().all(|c| (0.0..=100.0).contains(c)));
+                assert!((total_heat(f) - 100.0).abs() < 0.1, "{scheme:?}");
+                results.push(f.rows().flatten().copied().collect::<Vec<_>>());
+            });
+        }
+        let diff = results[0]
+            .iter()
+            .zip(&results[1])
+            .map(|(a, b)| (a - b).abs());
+        assert!(diff.fold(0.0, f32::max) < 1e-3);
+    }
+
+    #[test]
+    fn implicit_schemes_agree_next_to_fixed_sources() {
+        let source = HeatSource::Fixed {
+            x: 4,
+            y: 4,
+            value: 1.0,
+        };
+        let mut neighbours = vec![];
+        for scheme in [Scheme::Jacobi(400), Scheme::GaussSeidel(200)] {
+            let mut heat = Diffusion::new(1.0, 2.0)
+                .with_scheme(scheme)
+                .with_source(source);
+            let (src, mut dst) = (Grid::new(9, 9, 0.0), Grid::new(9, 9, 0.0));
+            heat.step(&src, &mut dst).unwrap();
+            assert_eq!(dst.get(4, 4), Ok(1.0));
+            let around = [(4, 3), (5, 4), (4, 5), (3, 4)].map(|(x, y)| dst[(x, y)]);
+            // Cells swept after the source see the held value just like those before it.
+            let spread = around.iter().map(|v| (v - around[0]).abs());
+            assert!(spread.fold(0.0, f32::max) < 1e-5, "{scheme:?}");
+            neighbours.push(around);
+        }
+        assert!((neighbours[0][0] - neighbours[1][0]).abs() < 1e-4);
+
+        let mut heat = Diffusion::new(1.0, 0.2);
+        let fresh = heat.clone();
+        heat.tick(&mut hot_spot());
+        assert_eq!(heat, fresh);
+    }
+
+    #[test]
+    fn sources() {
+        let mut heat = Diffusion::new(0.5, 0.4)
+            .with_source(HeatSource::Rate {
+                x: 1,
+                y: 1,
+                rate: 5.0,
+            })
+            .with_source(HeatSource::Fixed {
+                x: 7,
+                y: 7,
+                value: 3.0,
+            })
+            .with_source(HeatSource::Rate {
+                x: 99,
+                y: 0,
+                rate: 1.0,
+            });
+        let mut buf = DoubleBuf::<f32, 9, 9>::new();
+        heat.tick(&mut buf);
+        buf.render(|f| {
+            assert_eq!(f.get(7, 7), Ok(3.0));
+            assert!((total_heat(f) - (5.0 * 0.4 + 3.0)).abs() < 1e-4);
+        });
+
+        let mut small = Grid::new(3, 3, 0.0);
+        assert_eq!(
+            heat.step(&Grid::new(2, 2, 0.0), &mut small),
+            Err(BufferError::SizeMismatch)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Distance transforms: how far every cell is from the nearest of a set of seed cells, and
+//! which seed that is (its Voronoi region).
+//!
+//! All three metrics take time linear in the number of cells however many seeds there are.
+//! Manhattan and Chebyshev use the two-pass chamfer sweep, which is exact for them, and
+//! Euclidean the Felzenszwalb-Huttenlocher lower envelope of parabolas, a column pass then a
+//! row pass.
+
+use crate::buf::{Grid, TwoDeeBuffer, TwoDeeRead};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Metric {
+    /// Straight line distance.
+    #[default]
+    Euclidean,
+    /// `dx + dy`, steps moving up, down, left and right.
+    Manhattan,
+    /// `max(dx, dy)`, steps moving diagonally too.
+    Chebyshev,
+}
+
+impl Metric {
+    pub fn distance(self, a: (usize, usize), b: (usize, usize)) -> f32 {
+        let (dx, dy) = (a.0.abs_diff(b.0), a.1.abs_diff(b.1));
+        match self {
+            Metric::Euclidean => ((dx * dx + dy * dy) as f32).sqrt(),
+            Metric::Manhattan => (dx + dy) as f32,
+            Metric::Chebyshev => dx.max(dy) as f32,
+        }
+    }
+}
+
+/// Distances to the nearest seed, kept around so it can be recomputed each tick without
+/// allocating.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DistanceField {
+    pub metric: Metric,
+    /// Distance from each cell to its nearest seed, `f32::INFINITY` if there are no seeds.
+    pub distances: Grid<f32>,
+    /// Which seed is nearest each cell, counting from 1 in the order the seeds were given, or
+    /// 0 if there are no seeds. Cells as near to two seeds get either.
+    pub nearest: Grid<usize>,
+    /// Euclidean scratch: squared distances and seed ids after the column pass, and the
+    /// column being worked on.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    columns: Vec<(f64, usize)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    line: Vec<(f64, usize)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    envelope: Envelope,
+}
+
+/// Compares the results, not the scratch space.
+impl PartialEq for DistanceField {
+    fn eq(&self, other: &Self) -> bool {
+        self.metric == other.metric
+            && self.distances == other.distances
+            && self.nearest == other.nearest
+    }
+}
+
+impl DistanceField {
+    pub fn new(width: usize, height: usize, metric: Metric) -> Self {
+        Self {
+            metric,
+            distances: Grid::new(width, height, f32::INFINITY),
+            nearest: Grid::new(width, height, 0),
+            columns: vec![],
+            line: vec![],
+            envelope: Envelope::default(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.distances.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.distances.height()
+    }
+
+    pub fn distance(&self, x: usize, y: usize) -> Option<f32> {
+        self.distances.get(x, y).ok().filter(|d| d.is_finite())
+    }
+
+    /// Recomputes every cell's distance from `seeds`. Seeds off the grid are ignored.
+    pub fn compute(&mut self, seeds: &[(usize, usize)]) {
+        self.distances.fill(f32::INFINITY);
+        self.nearest.fill(0);
+        for (i, &(x, y)) in seeds.iter().enumerate() {
+            if self.nearest.get(x, y) == Ok(0) {
+                self.distances.set(x, y, 0.0).unwrap();
+                self.nearest.set(x, y, i + 1).unwrap();
+            }
+        }
+        match self.metric {
+            Metric::Euclidean => self.euclidean(),
+            Metric::Manhattan => self.chamfer(&[(-1, 0), (0, -1)]),
+            Metric::Chebyshev => self.chamfer(&[(-1, 0), (-1, -1), (0, -1), (1, -1)]),
+        }
+    }
+
+    /// Recomputes with every cell of `grid` that `is_seed` accepts as a seed, in reading
+    /// order.
+    pub fn compute_from<C, B>(&mut self, grid: &B, is_seed: impl Fn(&C) -> bool)
+    where
+        C: Copy,
+        B: TwoDeeRead<C> + ?Sized,
+    {
+        let seeds: Vec<_> = grid
+            .iter_coords()
+            .filter(|(_, c)| is_seed(c))
+            .map(|(pos, _)| pos)
+            .collect();
+        self.compute(&seeds);
+    }
+
+    /// Sweeps forwards with the steps in `back` (those pointing at cells already visited),
+    /// then backwards with them flipped, each step costing 1.
+    fn chamfer(&mut self, back: &[(isize, isize)]) {
+        let (w, h) = (self.width(), self.height());
+        let mut relax = |x: usize, y: usize, flip: isize| {
+            for &(dx, dy) in back {
+                let (Some(nx), Some(ny)) = (
+                    x.checked_add_signed(dx * flip),
+                    y.checked_add_signed(dy * flip),
+                ) else {
+                    continue;
+                };
+                if nx >= w || ny >= h {
+                    continue;
+                }
+                let via = self.distances.row(ny)[nx] + 1.0;
+                if via < self.distances.row(y)[x] {
+                    self.distances.row_mut(y)[x] = via;
+                    self.nearest.row_mut(y)[x] = self.nearest.row(ny)[nx];
+                }
+            }
+        };
+        for y in 0..h {
+            for x in 0..w {
+                relax(x, y, 1);
+            }
+        }
+        for y in (0..h).rev() {
+            for x in (0..w).rev() {
+                relax(x, y, -1);
+            }
+        }
+    }
+
+    fn euclidean(&mut self) {
+        let (w, h) = (self.width(), self.height());
+        self.columns.clear();
+        self.columns.resize(w * h, (f64::INFINITY, 0));
+
+        // Columns first: squared distance to the nearest seed in the same column.
+        for x in 0..w {
+            self.line.clear();
+            self.line.extend(self.nearest.column(x).map(|&id| {
+                let d = if id == 0 { f64::INFINITY } else { 0.0 };
+                (d, id)
+            }));
+            self.envelope
+                .transform(&self.line, |y, found| self.columns[y * w + x] = found);
+        }
+        // Then rows, over the column results.
+        for y in 0..h {
+            let row = &self.columns[y * w..(y + 1) * w];
+            let (distances, nearest) = (self.distances.row_mut(y), self.nearest.row_mut(y));
+            self.envelope.transform(row, |x, (d, id)| {
+                distances[x] = d.sqrt() as f32;
+                nearest[x] = id;
+            });
+        }
+    }
+}
+
+/// The lower envelope of the parabolas `(q - p)^2 + f(p)`, reused between lines.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Envelope {
+    /// Where each parabola in the envelope is rooted.
+    roots: Vec<usize>,
+    /// Where each parabola takes over from the one before.
+    starts: Vec<f64>,
+}
+
+impl Envelope {
+    /// For every `q`, finds the minimum over `p` of `(q - p)^2 + f[p].0` and passes it to
+    /// `out` along with `f[p].1`. Infinite entries are skipped.
+    fn transform(&mut self, f: &[(f64, usize)], mut out: impl FnMut(usize, (f64, usize))) {
+        self.roots.clear();
+        self.starts.clear();
+        let height = |p: usize| f[p].0 + (p * p) as f64;
+        for (q, &(fq, _)) in f.iter().enumerate() {
+            if fq.is_infinite() {
+                continue;
+            }
+            // Drop parabolas that the new one hides completely.
+            let mut start = f64::NEG_INFINITY;
+            while let Some(&p) = self.roots.last() {
+                start = (height(q) - height(p)) / (2 * (q - p)) as f64;
+                if start > *self.starts.last().unwrap() {
+                    break;
+                }
+                self.roots.pop();
+                self.starts.pop();
+                start = f64::NEG_INFINITY;
+            }
+            self.roots.push(q);
+            self.starts.push(start);
+        }
+
+        let mut k = 0;
+        for q in 0..f.len() {
+            if self.roots.is_empty() {
+                out(q, (f64::INFINITY, 0));
+                continue;
+            }
+            while k + 1 < self.roots.len() && self.starts[k + 1] <= q as f64 {
+                k += 1;
+            }
+            let p = self.roots[k];
+            let d = q.abs_diff(p) as f64;
+            out(q, (d * d + f[p].0, f[p].1));
+        }
+    }
+}
+
+/// Distances from `seeds` over a `width` x `height` grid.
+pub fn distance_transform(
+    width: usize,
+    height: usize,
+    seeds: &[(usize, usize)],
+    metric: Metric,
+) -> DistanceField {
+    let mut field = DistanceField::new(width, height, metric);
+    field.compute(seeds);
+    field
+}
+
+/// The Voronoi regions of `seeds`: each cell labelled with its nearest seed, counting from 1.
+pub fn voronoi(
+    width: usize,
+    height: usize,
+    seeds: &[(usize, usize)],
+    metric: Metric,
+) -> Grid<usize> {
+    distance_transform(width, height, seeds, metric).nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToText;
+    use rand::prelude::*;
+
+    const METRICS: [Metric; 3] = [Metric::Euclidean, Metric::Manhattan, Metric::Chebyshev];
+
+    #[test]
+    fn single_seed() {
+        let corner = |metric| distance_transform(7, 7, &[(3, 3)], metric).distance(0, 0);
+        assert_eq!(corner(Metric::Manhattan), Some(6.0));
+        assert_eq!(corner(Metric::Chebyshev), Some(3.0));
+        assert_eq!(corner(Metric::Euclidean), Some(18f32.sqrt()));
+
+        let mut field = DistanceField::new(4, 3, Metric::Euclidean);
+        field.compute(&[]);
+        assert_eq!(field.distance(1, 1), None);
+        assert_eq!(field.nearest.get(1, 1), Ok(0));
+        field.compute_from(
+            &Grid::parse("....\n...#\n....", |c| c == '#').unwrap(),
+            |c| *c,
+        );
+        assert_eq!(field.distance(0, 1), Some(3.0));
+        assert_eq!(field.nearest.get(0, 0), Ok(1));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for metric in METRICS {
+            let (w, h) = (23, 17);
+            let seeds: Vec<_> = (0..12)
+                .map(|_| (rng.gen_range(0..w), rng.gen_range(0..h)))
+                .collect();
+            let field = distance_transform(w, h, &seeds, metric);
+            for ((x, y), &d) in field.distances.iter_coords() {
+                let best = seeds
+                    .iter()
+                    .map(|&s| metric.distance((x, y), s))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(
+                    (d - best).abs() < 1e-4,
+                    "{metric:?} at {x},{y}: {d} vs {best}"
+                );
+                let id = field.nearest.get(x, y).unwrap();
+                let seed = seeds[id - 1];
+                assert!((metric.distance((x, y), seed) - best).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn voronoi_halves() {
+        let regions = voronoi(6, 2, &[(0, 0), (5, 0)], Metric::Manhattan);
+        assert_eq!(
+            regions.to_text(|id| char::from_digit(id as u32, 10).unwrap()),
+            "111222\n111222\n"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::buf::TwoDeeBuffer;
+
+/// Rasterised shape drawing for any grid. Points use signed coordinates so shapes can hang
+/// off the edges; anything outside the grid is clipped.
+pub trait Draw<C: Copy>: TwoDeeBuffer<C> {
+    /// Sets a single cell if it's on the grid, returning whether it was.
+    fn plot(&mut self, x: isize, y: isize, v: C) -> bool {
+        self.set(x, y, v).is_ok()
+    }
+
+    /// Bresenham line between two points, both ends included.
+    fn draw_line(&mut self, from: (isize, isize), to: (isize, isize), v: C) {
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let sx = if x < to.0 { 1 } else { -1 };
+        let sy = if y < to.1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.plot(x, y, v);
+            if (x, y) == to {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Midpoint circle outline.
+    fn draw_circle(&mut self, centre: (isize, isize), radius: usize, v: C) {
+        let (cx, cy) = centre;
+        let mut x = radius as isize;
+        let mut y = 0;
+        let mut err = 1 - x;
+        while x >= y {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.plot(cx + px, cy + py, v);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Closed outline through `points`, joining the last back to the first.
+    fn draw_polygon(&mut self, points: &[(isize, isize)], v: C) {
+        for (i, from) in points.iter().enumerate() {
+            let to = points[(i + 1) % points.len()];
+            self.draw_line(*from, to, v);
+        }
+    }
+}
+
+impl<C: Copy, B: TwoDeeBuffer<C>> Draw<C> for B {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Grid, Rect, ToText, Transform, TwoDeeRead};
+
+    fn to_text(grid: &Grid<u8>) -> Vec<String> {
+        grid.rows()
+            .map(|row| row.iter().map(|c| if *c == 1 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn lines_are_clipped() {
+        let mut grid = Grid::new(5, 3, 0u8);
+        grid.draw_line((-2, -2), (4, 4), 1);
+        assert_eq!(to_text(&grid), vec!["#....", ".#...", "..#.."]);
+    }
+
+    #[test]
+    fn circle_is_symmetric() {
+        let mut grid = Grid::new(7, 7, 0u8);
+        grid.draw_circle((3, 3), 3, 1);
+        let text = to_text(&grid);
+        assert_eq!(text[0], "..###..");
+        assert_eq!(text[3], "#.....#");
+        assert_eq!(text[6], "..###..");
+    }
+
+    #[test]
+    fn polygon_and_bulk_ops() {
+        let mut grid = Grid::new(4, 4, 0u8);
+        grid.draw_polygon(&[(0, 0), (3, 0), (3, 3), (0, 3)], 1);
+        assert_eq!(to_text(&grid), vec!["####", "#..#", "#..#", "####"]);
+
+        grid.fill_rect(Rect::new(1, 1, 10, 10), 1);
+        assert!(grid.iter_coords().all(|(_, c)| *c == 1));
+
+        let mut small = Grid::new(2, 2, 0u8);
+        let written = grid.blit(&small, Rect::of_size(2, 2), (3, 3));
+        assert_eq!(written, Rect::new(3, 3, 1, 1));
+        assert_eq!(grid[(3, 3)], 0);
+
+        small.fill(1);
+        assert!(grid.copy_from(&small).is_err());
+    }
+
+    #[test]
+    fn bulk_ops_off_the_grid() {
+        let mut grid = Grid::new(4, 4, 0u8);
+        grid.fill_rect(Rect::new(10, 0, 2, 2), 1);
+        grid.fill_rect(Rect::new(0, 10, 2, 2), 1);
+        grid.fill_rect(Rect::new(4, 4, 3, 3), 1);
+        assert!(grid.iter_coords().all(|(_, c)| *c == 0));
+
+        let small = Grid::new(2, 2, 1u8);
+        for (src_rect, dst) in [
+            (Rect::of_size(2, 2), (10, 1)),
+            (Rect::of_size(2, 2), (1, 10)),
+            (Rect::new(5, 0, 2, 2), (0, 0)),
+            (Rect::new(0, 5, 2, 2), (0, 0)),
+        ] {
+            assert!(grid.blit(&small, src_rect, dst).is_empty());
+        }
+        assert!(grid.iter_coords().all(|(_, c)| *c == 0));
+    }
+
+    #[test]
+    fn huge_rects_clip() {
+        let mut grid = Grid::new(4, 4, 0u8);
+        let max = usize::MAX;
+        grid.fill_rect(Rect::new(max - 1, max - 1, 5, 5), 1);
+        assert!(grid.iter_coords().all(|(_, c)| *c == 0));
+        grid.fill_rect(Rect::new(2, 3, max, max), 1);
+        assert_eq!(
+            grid.to_text(|c| char::from(b'0' + c)),
+            "0000\n0000\n0000\n0011\n"
+        );
+
+        let small = Grid::new(2, 2, 2u8);
+        assert!(grid
+            .blit(&small, Rect::new(1, 1, max, max), (max, max))
+            .is_empty());
+        assert!(grid
+            .blit(&small, Rect::of_size(2, 2), (max - 1, 0))
+            .is_empty());
+        assert_eq!(Rect::new(max, 0, 1, 1).right(), max);
+        assert!(grid.crop(Rect::new(1, 1, max, 1)).is_err());
+    }
+}
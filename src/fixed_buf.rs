@@ -1,16 +1,19 @@
+use std::ops::{Index, IndexMut};
 use std::sync::Arc;
 
-use num::{Integer, Num, ToPrimitive};
-use crate::buf::{BufferError, TwoDeeBuffer, Flipper};
+use bytemuck::Pod;
+use crate::buf::{rows_of, BufferError, TwoDeeBuffer, TwoDeeRead, Flipper, Publisher};
+use crate::rect::Rect;
+use crate::view::{View, ViewMut};
 
-pub struct FixedTwoDeeBuffer<C: Num + Copy, const W: usize, const H: usize> {
+pub struct FixedTwoDeeBuffer<C: Copy, const W: usize, const H: usize> {
     // It'd be neato if we could have this as a fixed-size array
     // but we can't use those generic values in const expressions.
     // despite the fact, they are very const
     pub buf: Vec<C>,
 }
 
-impl<C: Num + Copy, const W: usize, const H: usize>  FixedTwoDeeBuffer<C, W, H> {
+impl<C: Copy, const W: usize, const H: usize>  FixedTwoDeeBuffer<C, W, H> {
     pub fn new(initial: C) -> Self {
         let buf = vec![initial; W * H];
         Self { buf }
@@ -25,6 +28,7 @@ impl<C: Num + Copy, const W: usize, const H: usize>  FixedTwoDeeBuffer<C, W, H>
         size_of::<C>() * W
     }
 
+    // Also on `TwoDeeRead`, kept here so callers needn't import the trait.
     pub fn height(&self) -> usize {
         H
     }
@@ -36,67 +40,169 @@ impl<C: Num + Copy, const W: usize, const H: usize>  FixedTwoDeeBuffer<C, W, H>
     pub const fn len() -> usize {
         W * H
     }
+
+    /// Read-only window onto `rect`, in local coordinates.
+    pub fn view(&self, rect: Rect) -> Result<View<'_, C>, BufferError> {
+        View::new(&self.buf, W, rect)
+    }
+
+    /// Mutable window onto `rect`, in local coordinates.
+    pub fn view_mut(&mut self, rect: Rect) -> Result<ViewMut<'_, C>, BufferError> {
+        ViewMut::new(&mut self.buf, W, rect)
+    }
+
+    /// Splits the buffer into disjoint mutable tiles, see `ViewMut::tiles`.
+    pub fn tiles_mut(&mut self, tile_width: usize, tile_height: usize) -> Vec<ViewMut<'_, C>> {
+        ViewMut::new(&mut self.buf, W, Rect::of_size(W, H))
+            .map(|v| v.tiles(tile_width, tile_height))
+            .unwrap_or_default()
+    }
+}
+
+impl<C: Pod, const W: usize, const H: usize> FixedTwoDeeBuffer<C, W, H> {
+    /// Raw view of the cells, for uploading to the GPU.
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.buf)
+    }
+}
+
+impl<C: Copy, const W: usize, const H: usize> TwoDeeRead<C> for FixedTwoDeeBuffer<C, W, H> {
+    fn width(&self) -> usize {
+        W
+    }
+
+    fn height(&self) -> usize {
+        H
+    }
+
+    fn row(&self, y: usize) -> &[C] {
+        &self.buf[y * W..(y + 1) * W]
+    }
+
+    unsafe fn get_unchecked(&self, x: usize, y: usize) -> C {
+        *self.buf.get_unchecked(y * W + x)
+    }
 }
 
-impl<C: Num + Copy, const W: usize, const H: usize> TwoDeeBuffer<C> for FixedTwoDeeBuffer<C, W, H> {
-    /// Safely retrieves a value from the buffer, for the given x,y coords.
-    fn get<T: ToPrimitive>(&self, x: T, y: T) -> Result<C, BufferError> {
-        let x = x.to_usize().ok_or(BufferError::BadIndex)?;
-        let y = y.to_usize().ok_or(BufferError::BadIndex)?;
-        let i = y * self.width() + x;
+impl<C: Copy, const W: usize, const H: usize> TwoDeeBuffer<C> for FixedTwoDeeBuffer<C, W, H> {
+    fn row_mut(&mut self, y: usize) -> &mut [C] {
+        &mut self.buf[y * W..(y + 1) * W]
+    }
 
-        self.buf.get(i).copied().ok_or(BufferError::OutOfBounds)
+    fn rows_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut [C]>
+    where
+        C: 'a,
+    {
+        rows_of(&mut self.buf, W, H)
     }
 
-    /// Safely sets a value to the buffer, for the given x,y coords.
-    fn set<T: ToPrimitive>(&mut self, x: T, y: T, v: C) -> Result<(), BufferError> {
-        let x = x.to_usize().ok_or(BufferError::BadIndex)?;
-        let y = y.to_usize().ok_or(BufferError::BadIndex)?;
-        let i = y * self.height() + x;
+    unsafe fn set_unchecked(&mut self, x: usize, y: usize, v: C) {
+        *self.buf.get_unchecked_mut(y * W + x) = v;
+    }
+}
 
-        if i > self.width() * self.height() {
-            return Err(BufferError::OutOfBounds);
-        }
+impl<C: Copy, const W: usize, const H: usize> Index<(usize, usize)> for FixedTwoDeeBuffer<C, W, H> {
+    type Output = C;
 
-        self.buf[i] = v;
+    fn index(&self, (x, y): (usize, usize)) -> &C {
+        &self.row(y)[x]
+    }
+}
 
-        Ok(())
+impl<C: Copy, const W: usize, const H: usize> IndexMut<(usize, usize)> for FixedTwoDeeBuffer<C, W, H> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut C {
+        &mut self.row_mut(y)[x]
     }
 }
 
 // We could use a seperate ref that has render, and another that has reader, so that the user
 // has a harder time to misues the buffers.
 #[derive(Clone)]
-pub struct DoubleBuf<const W: usize, const H: usize>(Arc<Flipper<FixedTwoDeeBuffer<u32, W, H>, u32>>);
+pub struct DoubleBuf<C: Copy, const W: usize, const H: usize>(Arc<Flipper<FixedTwoDeeBuffer<C, W, H>, C>>);
 
-impl<const W: usize, const H: usize> DoubleBuf<W, H> {
-    pub fn new() -> Self {
-        let a = FixedTwoDeeBuffer::new(0);
-        let b = FixedTwoDeeBuffer::new(0);
+impl<C: Copy, const W: usize, const H: usize> DoubleBuf<C, W, H> {
+    /// Both buffers start out filled with `initial`.
+    pub fn with_value(initial: C) -> Self {
+        let a = FixedTwoDeeBuffer::new(initial);
+        let b = FixedTwoDeeBuffer::new(initial);
         let f = Flipper::new(a, b);
         Self(Arc::new(f))
     }
 
     // Uses the front buffer, which is safe for read-only access.
-    pub fn render<F: Fn(&FixedTwoDeeBuffer<u32, W, H>)>(&self, render_func: F) {
-        let f = self.0.front();
-        render_func(f);
+    pub fn render<F: FnOnce(&FixedTwoDeeBuffer<C, W, H>)>(&self, render_func: F) {
+        render_func(&self.0.front());
     }
 
     // Uses the back buffer, which is not read from.
-    pub fn update<F: Fn(&mut FixedTwoDeeBuffer<u32, W, H>)>(&mut self, update_func: F) {
-        let x = self.0.clone();
-        // SAFETY: Operations only ever occur on the back buffer. Buffers are swapped via
-        // an atomic pointer, via flip.
-        unsafe {
-            let ptr = Arc::into_raw(x) as *mut Flipper<FixedTwoDeeBuffer<u32, W, H>, u32>;
-            let buf = (*ptr).back();
-            update_func(buf);
-            (*ptr).flip();
-        }
+    pub fn update<F: FnOnce(&mut FixedTwoDeeBuffer<C, W, H>)>(&mut self, update_func: F) {
+        self.step(|_, back| update_func(back));
+    }
+
+    /// Reads the front buffer while writing the back one, then flips. For sims that work out
+    /// each frame from the last.
+    pub fn step<F>(&mut self, step_func: F)
+    where
+        F: FnOnce(&FixedTwoDeeBuffer<C, W, H>, &mut FixedTwoDeeBuffer<C, W, H>),
+    {
+        self.0.step_shared(step_func);
     }
 
     pub const fn buf_size() -> usize {
-        FixedTwoDeeBuffer::<u8, W, H>::size()
+        FixedTwoDeeBuffer::<C, W, H>::size()
+    }
+}
+
+impl<C: Copy, const W: usize, const H: usize> Publisher<C> for DoubleBuf<C, W, H> {
+    type Frame = FixedTwoDeeBuffer<C, W, H>;
+
+    fn publish<F: FnOnce(&mut Self::Frame)>(&mut self, f: F) {
+        self.update(f);
+    }
+
+    fn step<F: FnOnce(&Self::Frame, &mut Self::Frame)>(&mut self, f: F) {
+        DoubleBuf::step(self, f);
+    }
+
+    fn render<F: FnOnce(&Self::Frame)>(&self, f: F) {
+        DoubleBuf::render(self, f);
+    }
+}
+
+impl<C: Copy + Default, const W: usize, const H: usize> DoubleBuf<C, W, H> {
+    pub fn new() -> Self {
+        Self::with_value(C::default())
+    }
+}
+
+impl<C: Copy + Default, const W: usize, const H: usize> Default for DoubleBuf<C, W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Cell {
+        Wall,
+        Open { visited: bool },
+    }
+
+    #[test]
+    fn stores_non_numeric_cells() {
+        let mut buf = FixedTwoDeeBuffer::<Cell, 4, 3>::new(Cell::Wall);
+        buf.set(1, 2, Cell::Open { visited: true }).unwrap();
+        assert_eq!(buf.get(1, 2).unwrap(), Cell::Open { visited: true });
+        assert_eq!(buf.get(0, 0).unwrap(), Cell::Wall);
+    }
+
+    #[test]
+    fn double_buf_of_bools() {
+        let mut buf = DoubleBuf::<bool, 5, 5>::new();
+        buf.update(|f| f.buf.fill(true));
+        buf.render(|f| assert!(f.buf.iter().all(|c| *c)));
     }
 }
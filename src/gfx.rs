@@ -1,33 +1,46 @@
-use crate::FixedTwoDeeBuffer;
+
+use crate::{BufferHandle, DiffOverlay, DoubleBuf};
 use encase::ShaderType;
 use glam::{vec2, Vec2};
+use std::cell::RefCell;
+use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Instant;
-use tracing::warn;
+use tracing::{info, warn};
 use wgpu::{
     include_wgsl, BindGroup, Buffer, Device, Queue, RenderPipeline, Surface, SurfaceConfiguration,
 };
 use winit::application::ApplicationHandler;
 use winit::event::*;
 use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{Key, NamedKey};
 use winit::window::{Window, WindowId};
 
-// Uniform buffer.
-#[derive(Debug, Default, ShaderType)] // this baby can fit so many derive macros
-struct State {
-    pub cursor_pos: glam::Vec2,
-    pub dimensions: glam::Vec2,
-    pub time: f32,
-    pub grid_dim: glam::UVec2, // TODO: This is going to remain constant, so maybe a bad fit.
-}
+// Uniform buffer. Kept in its own module so the allow below only covers the structs that
+// need it: encase's ShaderType derive emits per-field `check` fns that newer rustc flags as
+// dead.
+mod uniforms {
+    #![allow(dead_code)]
 
-impl State {
-    fn as_wgsl_bytes(&self) -> encase::internal::Result<Vec<u8>> {
-        let mut buffer = encase::UniformBuffer::new(Vec::new());
-        buffer.write(self)?;
-        Ok(buffer.into_inner())
+    use encase::ShaderType;
+
+    #[derive(Debug, Default, ShaderType)] // this baby can fit so many derive macros
+    pub(super) struct State {
+        pub cursor_pos: glam::Vec2,
+        pub dimensions: glam::Vec2,
+        pub time: f32,
+        pub grid_dim: glam::UVec2, // TODO: This is going to remain constant, so maybe a bad fit.
+    }
+
+    impl State {
+        pub(super) fn as_wgsl_bytes(&self) -> encase::internal::Result<Vec<u8>> {
+            let mut buffer = encase::UniformBuffer::new(Vec::new());
+            buffer.write(self)?;
+            Ok(buffer.into_inner())
+        }
     }
 }
+use uniforms::State;
 
 // UHH NOT THE STATE
 // https://www.youtube.com/watch?v=rGV0E7f8zeg
@@ -42,17 +55,122 @@ struct Context<'a> {
     grid_buffer: Buffer,
 }
 
-pub struct App<'a> {
+/// Something the viewer can pull frames from. Sources of `u32` cells are shown as packed
+/// colours; anything else goes through `coloured` first.
+pub trait FrameSource<C: Copy = u32> {
+    /// Grid size in cells, as (width, height).
+    fn dimensions(&self) -> (usize, usize);
+
+    /// Hands the current front buffer to `f`, cells in row-major order.
+    fn with_frame<F: FnOnce(&[C])>(&self, f: F);
+
+    /// Pauses or resumes the sim feeding this source, returning whether it is now paused.
+    /// Sources that can't pause ignore it.
+    fn toggle_pause(&self) -> bool {
+        false
+    }
+
+    /// Moves the shown frame `steps` frames through history while paused, returning the new
+    /// (position, frames held). `None` if the source keeps no history or isn't paused.
+    fn scrub(&self, _steps: isize) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Calls `changed` with the row-major index of every cell changed by frames published
+    /// after the first `seen`, then moves `seen` up to the newest. Returns `false` if the
+    /// source doesn't keep track, in which case the viewer diffs the frames it draws instead.
+    fn changes_since<F: FnMut(usize)>(&self, _seen: &mut usize, _changed: F) -> bool {
+        false
+    }
+
+    /// Shows this source by mapping each cell to a packed colour.
+    fn coloured<F: Fn(C) -> u32>(self, colour: F) -> Coloured<Self, C, F>
+    where
+        Self: Sized,
+    {
+        Coloured {
+            source: self,
+            colour,
+            scratch: RefCell::new(vec![]),
+            _cell: PhantomData,
+        }
+    }
+}
+
+impl<C: Copy, const W: usize, const H: usize> FrameSource<C> for DoubleBuf<C, W, H> {
+    fn dimensions(&self) -> (usize, usize) {
+        (W, H)
+    }
+
+    fn with_frame<F: FnOnce(&[C])>(&self, f: F) {
+        self.render(|buf| f(&buf.buf));
+    }
+}
+
+impl<C: Copy> FrameSource<C> for BufferHandle<C> {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+
+    fn with_frame<F: FnOnce(&[C])>(&self, f: F) {
+        self.render(|buf| f(buf.cells()));
+    }
+}
+
+/// A `FrameSource` of any cell type, converted to colours as frames are pulled. Made by
+/// `FrameSource::coloured`.
+pub struct Coloured<S, C, F> {
+    source: S,
+    colour: F,
+    scratch: RefCell<Vec<u32>>,
+    _cell: PhantomData<C>,
+}
+
+impl<C: Copy, S: FrameSource<C>, F: Fn(C) -> u32> FrameSource for Coloured<S, C, F> {
+    fn dimensions(&self) -> (usize, usize) {
+        self.source.dimensions()
+    }
+
+    fn with_frame<G: FnOnce(&[u32])>(&self, f: G) {
+        let mut scratch = self.scratch.borrow_mut();
+        self.source.with_frame(|cells| {
+            scratch.clear();
+            scratch.extend(cells.iter().map(|c| (self.colour)(*c)));
+        });
+        f(&scratch);
+    }
+
+    fn toggle_pause(&self) -> bool {
+        self.source.toggle_pause()
+    }
+
+    fn scrub(&self, steps: isize) -> Option<(usize, usize)> {
+        self.source.scrub(steps)
+    }
+
+    fn changes_since<G: FnMut(usize)>(&self, seen: &mut usize, changed: G) -> bool {
+        self.source.changes_since(seen, changed)
+    }
+}
+
+pub struct App<'a, S: FrameSource = crate::MyBuf> {
     window: Option<Arc<Window>>, // AHHH I SEE, ARCS ARE TAXATION
     ctx: Option<Context<'a>>,
     state: State,
     start: std::time::Instant,
-    buf: crate::MyBuf,
+    last_redraw: Instant,
+    overlay: Option<DiffOverlay>,
+    show_overlay: bool,
+    /// Frames published so far, as far as the overlay has seen.
+    seen: usize,
+    /// Cells changed by publishes since the last redraw.
+    changed: Vec<usize>,
+    buf: S,
 }
 
 // Higher level, where we wrap external state and internal gfx state.
-impl App<'_> {
-    pub fn new(buf: crate::MyBuf) -> Self {
+impl<S: FrameSource> App<'_, S> {
+    pub fn new(buf: S) -> Self {
         let window = None;
         let ctx = None;
         let state = State::default();
@@ -62,16 +180,28 @@ impl App<'_> {
             ctx,
             state,
             start,
+            last_redraw: start,
+            overlay: None,
+            show_overlay: false,
+            seen: 0,
+            changed: vec![],
             buf,
         }
     }
+
+    /// Highlights cells changed by each published frame. Toggle it with `D`.
+    pub fn with_diff_overlay(mut self, overlay: DiffOverlay) -> Self {
+        self.overlay = Some(overlay);
+        self.show_overlay = true;
+        self
+    }
 }
 
 // https://github.com/rust-windowing/winit/discussions/3667#discussioncomment-9329312
 impl<'a> Context<'a> {
     // Create from the window. Considering the window may not be created until resume, we defer
     // like so;
-    pub async fn new(window: Arc<Window>) -> Context<'a> {
+    pub async fn new(window: Arc<Window>, grid_dim: (usize, usize)) -> Context<'a> {
         let mut size = window.inner_size();
         size.width = size.width.max(1);
         size.height = size.height.max(1);
@@ -118,7 +248,7 @@ impl<'a> Context<'a> {
 
         let grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("double buf GPU side"),
-            size: FixedTwoDeeBuffer::<u32, 100, 100>::size() as u64, // Argh! we need to somehow pass this data in.
+            size: (grid_dim.0 * grid_dim.1 * size_of::<u32>()).max(4) as u64,
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
@@ -223,7 +353,7 @@ impl<'a> Context<'a> {
     }
 }
 
-impl ApplicationHandler for App<'_> {
+impl<S: FrameSource> ApplicationHandler for App<'_, S> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.window.is_none() {
             self.start = Instant::now();
@@ -234,9 +364,10 @@ impl ApplicationHandler for App<'_> {
             );
             self.window = Some(window.clone());
 
-            let state = pollster::block_on(Context::new(window.clone()));
+            let (w, h) = self.buf.dimensions();
+            let state = pollster::block_on(Context::new(window.clone(), (w, h)));
             self.ctx = Some(state);
-            self.state.grid_dim = glam::uvec2(50, 50);
+            self.state.grid_dim = glam::uvec2(w as u32, h as u32);
         }
     }
 
@@ -246,6 +377,47 @@ impl ApplicationHandler for App<'_> {
                 warn!("The close button was pressed; stopping");
                 event_loop.exit();
             }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let key = match logical_key {
+                    Key::Named(key) => key,
+                    Key::Character(c) if c.eq_ignore_ascii_case("d") => {
+                        if let Some(overlay) = self.overlay.as_mut() {
+                            overlay.reset();
+                            // Skip whatever was published while it was off.
+                            self.buf.changes_since(&mut self.seen, |_| ());
+                            self.show_overlay = !self.show_overlay;
+                            info!(self.show_overlay, "Toggled diff overlay");
+                        }
+                        return;
+                    }
+                    _ => return,
+                };
+                let steps = match key {
+                    NamedKey::Space => {
+                        let paused = self.buf.toggle_pause();
+                        info!(paused, "Toggled pause");
+                        return;
+                    }
+                    NamedKey::ArrowLeft => -1,
+                    NamedKey::ArrowRight => 1,
+                    NamedKey::PageUp => -10,
+                    NamedKey::PageDown => 10,
+                    NamedKey::Home => isize::MIN,
+                    NamedKey::End => isize::MAX,
+                    _ => return,
+                };
+                if let Some((position, len)) = self.buf.scrub(steps) {
+                    info!("Showing frame {} of {len}", position + 1);
+                }
+            }
             WindowEvent::Resized(new_size) => {
                 if let Some(state) = self.ctx.as_mut() {
                     // Reconfigure the surface with the new size
@@ -292,9 +464,24 @@ impl ApplicationHandler for App<'_> {
                         .device
                         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-                    self.buf.render(|f| {
-                        let casted = bytemuck::cast_slice(&f.buf);
-                        ctx.queue.write_buffer(&ctx.grid_buffer, 0, casted);
+                    let since_last = self.last_redraw.elapsed();
+                    self.last_redraw = Instant::now();
+                    let overlay = self.overlay.as_mut().filter(|_| self.show_overlay);
+                    // Collected before `with_frame`, which may hold the source's history.
+                    self.changed.clear();
+                    let changed = &mut self.changed;
+                    let tracked = overlay.is_some()
+                        && self.buf.changes_since(&mut self.seen, |i| changed.push(i));
+                    self.buf.with_frame(|cells| {
+                        let cells = match overlay {
+                            Some(overlay) if tracked => {
+                                overlay.apply_changes(cells, changed, since_last)
+                            }
+                            Some(overlay) => overlay.apply(cells, since_last),
+                            None => cells,
+                        };
+                        ctx.queue
+                            .write_buffer(&ctx.grid_buffer, 0, bytemuck::cast_slice(cells));
                     });
 
                     {
@@ -0,0 +1,452 @@
+//! Rewindable history of published frames.
+//!
+//! Only the oldest frame is kept whole; every later frame is stored as the list of cells that
+//! changed since the one before it, which for most sims is a small fraction of the grid.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::buf::{Grid, Publisher, TwoDeeRead};
+use crate::fixed_buf::{DoubleBuf, FixedTwoDeeBuffer};
+use crate::gfx::FrameSource;
+
+/// One cell write, kept with the value it replaced so a delta can be undone as well as redone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Change<C> {
+    index: usize,
+    old: C,
+    new: C,
+}
+
+/// Ring buffer of the last `capacity` frames, delta compressed against the previous frame.
+#[derive(Clone, Debug)]
+pub struct FrameHistory<C: Copy> {
+    capacity: usize,
+    /// The oldest frame still held.
+    base: Option<Grid<C>>,
+    /// The newest frame, which new pushes are diffed against.
+    latest: Option<Grid<C>>,
+    /// `deltas[i]` turns frame `i` into frame `i + 1`.
+    deltas: VecDeque<Vec<Change<C>>>,
+    /// Frame being looked at while scrubbing, `None` when following the newest frame.
+    cursor: Option<(usize, Grid<C>)>,
+    /// Frames ever pushed, so callers can ask what changed since they last looked.
+    pushed: usize,
+}
+
+impl<C: Copy + PartialEq> FrameHistory<C> {
+    /// Keeps up to `capacity` frames (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            base: None,
+            latest: None,
+            deltas: VecDeque::new(),
+            cursor: None,
+            pushed: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of frames held.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Total cell changes stored across all deltas, a rough measure of memory use.
+    pub fn stored_changes(&self) -> usize {
+        self.deltas.iter().map(Vec::len).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.base = None;
+        self.latest = None;
+        self.deltas.clear();
+        self.cursor = None;
+    }
+
+    /// Records a new newest frame, dropping the oldest once full. Frames of a different size
+    /// to the ones already held restart the history. Scrubbing snaps back to the newest frame.
+    pub fn push<B: TwoDeeRead<C>>(&mut self, frame: &B) {
+        self.cursor = None;
+        let Some(latest) = self.latest.as_mut() else {
+            let grid = Grid::from_vec(
+                frame.width(),
+                frame.height(),
+                frame.rows().flatten().copied().collect(),
+            )
+            .expect("rows match size");
+            self.base = Some(grid.clone());
+            self.latest = Some(grid);
+            self.pushed += 1;
+            return;
+        };
+        if (latest.width(), latest.height()) != (frame.width(), frame.height()) {
+            self.clear();
+            return self.push(frame);
+        }
+
+        let delta: Vec<_> = latest
+            .cells_mut()
+            .iter_mut()
+            .zip(frame.rows().flatten())
+            .enumerate()
+            .filter(|(_, (old, new))| **old != **new)
+            .map(|(index, (old, new))| {
+                let change = Change {
+                    index,
+                    old: *old,
+                    new: *new,
+                };
+                *old = *new;
+                change
+            })
+            .collect();
+        self.deltas.push_back(delta);
+        self.pushed += 1;
+
+        if self.deltas.len() >= self.capacity {
+            let dropped = self.deltas.pop_front().expect("at least one delta");
+            let base = self.base.as_mut().expect("base set with latest");
+            redo(base, &dropped);
+        }
+    }
+
+    /// Number of frames pushed so far, including any since dropped.
+    pub fn pushed(&self) -> usize {
+        self.pushed
+    }
+
+    /// Row-major indices of the cells changed by each frame pushed after the first `seen`,
+    /// oldest first. Cells changed by several frames show up once per frame, and frames
+    /// already dropped from the history are skipped.
+    pub fn changes_since(&self, seen: usize) -> impl Iterator<Item = usize> + '_ {
+        let newer = self.pushed.saturating_sub(seen).min(self.deltas.len());
+        self.deltas
+            .iter()
+            .skip(self.deltas.len() - newer)
+            .flatten()
+            .map(|change| change.index)
+    }
+
+    /// The newest frame.
+    pub fn latest(&self) -> Option<&Grid<C>> {
+        self.latest.as_ref()
+    }
+
+    /// Rebuilds frame `i`, counting from the oldest held at 0.
+    pub fn frame(&self, i: usize) -> Option<Grid<C>> {
+        if i >= self.len() {
+            return None;
+        }
+        let mut grid = self.base.clone()?;
+        for delta in self.deltas.iter().take(i) {
+            redo(&mut grid, delta);
+        }
+        Some(grid)
+    }
+
+    /// Index of the frame being looked at, which is `len() - 1` unless scrubbed back.
+    pub fn position(&self) -> usize {
+        self.cursor
+            .as_ref()
+            .map_or(self.len().saturating_sub(1), |(i, _)| *i)
+    }
+
+    /// The frame at `position()`.
+    pub fn current(&self) -> Option<&Grid<C>> {
+        match &self.cursor {
+            Some((_, grid)) => Some(grid),
+            None => self.latest.as_ref(),
+        }
+    }
+
+    /// Moves the cursor `steps` frames (negative is back in time), clamped to the frames held,
+    /// and returns the frame there. Each step only replays one delta.
+    pub fn step(&mut self, steps: isize) -> Option<&Grid<C>> {
+        let last = self.len().checked_sub(1)?;
+        let target = self.position().saturating_add_signed(steps).min(last);
+        self.seek(target)
+    }
+
+    /// Moves the cursor to frame `i`, clamped to the frames held.
+    pub fn seek(&mut self, i: usize) -> Option<&Grid<C>> {
+        let last = self.len().checked_sub(1)?;
+        let target = i.min(last);
+        if target == last {
+            self.cursor = None;
+            return self.latest.as_ref();
+        }
+
+        let (mut at, mut grid) = self
+            .cursor
+            .take()
+            .unwrap_or_else(|| (last, self.latest.clone().expect("not empty")));
+        // Walking from whichever end is nearer keeps long jumps cheap.
+        if target < at.abs_diff(target) {
+            (at, grid) = (0, self.base.clone().expect("not empty"));
+        }
+        while at > target {
+            at -= 1;
+            undo(&mut grid, &self.deltas[at]);
+        }
+        while at < target {
+            redo(&mut grid, &self.deltas[at]);
+            at += 1;
+        }
+        self.cursor = Some((at, grid));
+        self.current()
+    }
+
+    /// Stops scrubbing and follows the newest frame again.
+    pub fn follow_latest(&mut self) {
+        self.cursor = None;
+    }
+}
+
+fn redo<C: Copy>(grid: &mut Grid<C>, delta: &[Change<C>]) {
+    let cells = grid.cells_mut();
+    for change in delta {
+        cells[change.index] = change.new;
+    }
+}
+
+fn undo<C: Copy>(grid: &mut Grid<C>, delta: &[Change<C>]) {
+    let cells = grid.cells_mut();
+    for change in delta {
+        cells[change.index] = change.old;
+    }
+}
+
+struct Shared<C: Copy> {
+    history: Mutex<FrameHistory<C>>,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+/// A `DoubleBuf` that records every published frame into a `FrameHistory`, and can pause the
+/// sim thread so the viewer can scrub through what happened.
+#[derive(Clone)]
+pub struct HistoryBuf<C: Copy, const W: usize, const H: usize> {
+    buf: DoubleBuf<C, W, H>,
+    shared: Arc<Shared<C>>,
+}
+
+impl<C: Copy + PartialEq, const W: usize, const H: usize> HistoryBuf<C, W, H> {
+    /// Wraps `buf`, keeping its last `capacity` frames.
+    pub fn new(buf: DoubleBuf<C, W, H>, capacity: usize) -> Self {
+        Self {
+            buf,
+            shared: Arc::new(Shared {
+                history: Mutex::new(FrameHistory::new(capacity)),
+                paused: Mutex::new(false),
+                resumed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// The live front buffer, regardless of any scrubbing.
+    pub fn render<F: FnOnce(&FixedTwoDeeBuffer<C, W, H>)>(&self, render_func: F) {
+        self.buf.render(render_func);
+    }
+
+    /// Same as `DoubleBuf::update`, but blocks while paused and records the published frame.
+    pub fn update<F: FnOnce(&mut FixedTwoDeeBuffer<C, W, H>)>(&mut self, update_func: F) {
+        drop(self.wait_while_paused());
+        let shared = self.shared.clone();
+        self.buf.update(|back| {
+            update_func(back);
+            lock(&shared.history).push(back);
+        });
+    }
+
+    /// Same as `DoubleBuf::step`, but blocks while paused and records the published frame.
+    pub fn step<F>(&mut self, step_func: F)
+    where
+        F: FnOnce(&FixedTwoDeeBuffer<C, W, H>, &mut FixedTwoDeeBuffer<C, W, H>),
+    {
+        drop(self.wait_while_paused());
+        let shared = self.shared.clone();
+        self.buf.step(|front, back| {
+            step_func(front, back);
+            lock(&shared.history).push(back);
+        });
+    }
+
+    pub fn history(&self) -> MutexGuard<'_, FrameHistory<C>> {
+        lock(&self.shared.history)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *lock(&self.shared.paused)
+    }
+
+    /// Pausing holds the next `update` until resumed. Resuming snaps any scrubbing back to
+    /// the newest frame.
+    pub fn set_paused(&self, paused: bool) {
+        *lock(&self.shared.paused) = paused;
+        if !paused {
+            self.history().follow_latest();
+            self.shared.resumed.notify_all();
+        }
+    }
+
+    fn wait_while_paused(&self) -> MutexGuard<'_, bool> {
+        let paused = lock(&self.shared.paused);
+        self.shared
+            .resumed
+            .wait_while(paused, |paused| *paused)
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<C: Copy + PartialEq, const W: usize, const H: usize> Publisher<C> for HistoryBuf<C, W, H> {
+    type Frame = FixedTwoDeeBuffer<C, W, H>;
+
+    fn publish<F: FnOnce(&mut Self::Frame)>(&mut self, f: F) {
+        self.update(f);
+    }
+
+    fn step<F: FnOnce(&Self::Frame, &mut Self::Frame)>(&mut self, f: F) {
+        HistoryBuf::step(self, f);
+    }
+
+    fn render<F: FnOnce(&Self::Frame)>(&self, f: F) {
+        HistoryBuf::render(self, f);
+    }
+}
+
+/// A panicking sim thread shouldn't take the viewer down with it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<C: Copy + PartialEq, const W: usize, const H: usize> FrameSource<C> for HistoryBuf<C, W, H> {
+    fn dimensions(&self) -> (usize, usize) {
+        (W, H)
+    }
+
+    fn with_frame<F: FnOnce(&[C])>(&self, f: F) {
+        let history = self.history();
+        match history.current() {
+            Some(frame) if self.is_paused() => f(frame.cells()),
+            _ => {
+                drop(history);
+                self.buf.render(|buf| f(&buf.buf));
+            }
+        }
+    }
+
+    fn toggle_pause(&self) -> bool {
+        let paused = !self.is_paused();
+        self.set_paused(paused);
+        paused
+    }
+
+    fn scrub(&self, steps: isize) -> Option<(usize, usize)> {
+        if !self.is_paused() {
+            return None;
+        }
+        let mut history = self.history();
+        history.step(steps)?;
+        Some((history.position(), history.len()))
+    }
+
+    fn changes_since<F: FnMut(usize)>(&self, seen: &mut usize, changed: F) -> bool {
+        let history = self.history();
+        history.changes_since(*seen).for_each(changed);
+        *seen = history.pushed();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TwoDeeBuffer;
+
+    fn frame(values: [u8; 4]) -> Grid<u8> {
+        Grid::from_vec(2, 2, values.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn keeps_last_n_frames() {
+        let mut history = FrameHistory::new(3);
+        for i in 0..5u8 {
+            history.push(&frame([i, 0, 0, i]));
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.frame(0), Some(frame([2, 0, 0, 2])));
+        assert_eq!(history.frame(2), Some(frame([4, 0, 0, 4])));
+        assert_eq!(history.frame(3), None);
+        // Two cells change per frame, and only the two newer frames are deltas.
+        assert_eq!(history.stored_changes(), 4);
+    }
+
+    #[test]
+    fn scrubs_both_ways() {
+        let mut history = FrameHistory::new(10);
+        for i in 0..6u8 {
+            history.push(&frame([i, i / 2, 0, 0]));
+        }
+        assert_eq!(history.step(-2), Some(&frame([3, 1, 0, 0])));
+        assert_eq!(history.step(-100), Some(&frame([0, 0, 0, 0])));
+        assert_eq!(history.position(), 0);
+        assert_eq!(history.step(1), Some(&frame([1, 0, 0, 0])));
+        assert_eq!(history.seek(4), Some(&frame([4, 2, 0, 0])));
+        assert_eq!(history.step(100), Some(&frame([5, 2, 0, 0])));
+        assert_eq!(history.position(), 5);
+
+        history.step(-1);
+        history.push(&frame([9, 9, 9, 9]));
+        assert_eq!(history.current(), Some(&frame([9, 9, 9, 9])));
+    }
+
+    #[test]
+    fn changes_since_a_push() {
+        let mut history = FrameHistory::new(3);
+        history.push(&frame([0, 0, 0, 0]));
+        assert_eq!(history.changes_since(0).count(), 0);
+        // A change that is undone by the next frame is still reported.
+        history.push(&frame([1, 0, 0, 0]));
+        history.push(&frame([0, 0, 0, 2]));
+        assert_eq!(history.pushed(), 3);
+        assert_eq!(history.changes_since(1).collect::<Vec<_>>(), [0, 0, 3]);
+        assert_eq!(history.changes_since(2).collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(history.changes_since(3).count(), 0);
+
+        // Only what is still held comes back for a caller that fell behind.
+        history.push(&frame([0, 5, 0, 2]));
+        assert_eq!(history.changes_since(0).collect::<Vec<_>>(), [0, 3, 1]);
+    }
+
+    #[test]
+    fn pausing_holds_updates() {
+        let buf = HistoryBuf::new(DoubleBuf::<u32, 2, 2>::new(), 8);
+        buf.set_paused(true);
+        let mut sim = buf.clone();
+        let worker = std::thread::spawn(move || {
+            for i in 1..=3 {
+                sim.update(|f| f.fill(i));
+            }
+        });
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(buf.history().is_empty());
+
+        buf.set_paused(false);
+        worker.join().unwrap();
+        assert_eq!(buf.history().len(), 3);
+        buf.render(|f| assert_eq!(f.get(1, 1), Ok(3)));
+
+        buf.set_paused(true);
+        assert_eq!(buf.scrub(-1), Some((1, 3)));
+        buf.with_frame(|cells| assert_eq!(cells[0], 2));
+    }
+}
@@ -0,0 +1,367 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::buf::{BufferError, Grid, TwoDeeRead};
+use crate::colour::unpack_rgba;
+use crate::pack_rgba;
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("PNG decode error: {0}")]
+    PngDecode(#[from] png::DecodingError),
+    #[error("PNG encode error: {0}")]
+    PngEncode(#[from] png::EncodingError),
+    #[error("Unsupported image: {0}")]
+    Unsupported(String),
+    #[error("A {width}x{height} grid at scale {scale} is too large an image")]
+    TooLarge {
+        width: usize,
+        height: usize,
+        scale: usize,
+    },
+    #[error("Malformed PPM: {0}")]
+    BadPpm(&'static str),
+    #[error(transparent)]
+    Buffer(#[from] BufferError),
+}
+
+/// Loads a PNG or PPM, picked by extension, into a grid of `pack_rgba` colours.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<Grid<u32>, ImageError> {
+    let path = path.as_ref();
+    let file = BufReader::new(File::open(path)?);
+    match extension(path).as_str() {
+        "png" => read_png(file),
+        "ppm" => read_ppm(file),
+        other => Err(ImageError::Unsupported(format!("extension {other:?}"))),
+    }
+}
+
+/// Saves a grid to PNG or PPM, picked by extension, with each cell drawn as a
+/// `scale` x `scale` block. `colour` turns cells into `pack_rgba` colours; use `|c| c` for
+/// grids that already hold colours, or a `Colormap` for scalar ones.
+pub fn save_image<C, B, F, P>(grid: &B, path: P, scale: usize, colour: F) -> Result<(), ImageError>
+where
+    C: Copy,
+    B: TwoDeeRead<C>,
+    F: Fn(C) -> u32,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let kind = extension(path);
+    if kind != "png" && kind != "ppm" {
+        return Err(ImageError::Unsupported(format!("extension {kind:?}")));
+    }
+    let mut file = BufWriter::new(File::create(path)?);
+    match kind.as_str() {
+        "png" => write_png(grid, &mut file, scale, colour)?,
+        _ => write_ppm(grid, &mut file, scale, colour)?,
+    }
+    file.flush()?;
+    Ok(())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Decodes a PNG of any bit depth or colour type into `pack_rgba` colours.
+pub fn read_png<R: Read>(reader: R) -> Result<Grid<u32>, ImageError> {
+    let mut decoder = png::Decoder::new(reader);
+    // Expand palettes and low bit depths, and strip 16 bit down to 8.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut bytes)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let channels = info.color_type.samples();
+    let cells = bytes[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| match px {
+            [l] => pack_rgba(*l, *l, *l, 255),
+            [l, a] => pack_rgba(*l, *l, *l, *a),
+            [r, g, b] => pack_rgba(*r, *g, *b, 255),
+            [r, g, b, a] => pack_rgba(*r, *g, *b, *a),
+            _ => unreachable!("png has 1 to 4 samples per pixel"),
+        })
+        .collect();
+    Ok(Grid::from_vec(width, height, cells)?)
+}
+
+/// Encodes as 8 bit RGBA.
+pub fn write_png<C, B, F, W>(grid: &B, writer: W, scale: usize, colour: F) -> Result<(), ImageError>
+where
+    C: Copy,
+    B: TwoDeeRead<C>,
+    F: Fn(C) -> u32,
+    W: Write,
+{
+    let (width, height, pixels) = scaled_pixels(grid, scale, colour)?;
+    let too_large = |_| too_large(grid, scale);
+    let (width, height) = (
+        u32::try_from(width).map_err(too_large)?,
+        u32::try_from(height).map_err(too_large)?,
+    );
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let bytes: Vec<u8> = pixels.iter().flat_map(|c| unpack_rgba(*c)).collect();
+    writer.write_image_data(&bytes)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads binary (P6) or plain (P3) PPMs. Alpha is always opaque.
+pub fn read_ppm<R: BufRead>(mut reader: R) -> Result<Grid<u32>, ImageError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut pos = 0;
+
+    let magic = next_token(&bytes, &mut pos).ok_or(ImageError::BadPpm("missing magic"))?;
+    let binary = match magic {
+        b"P6" => true,
+        b"P3" => false,
+        _ => return Err(ImageError::BadPpm("expected P3 or P6")),
+    };
+    let mut header = [0usize; 3];
+    for field in header.iter_mut() {
+        let token = next_token(&bytes, &mut pos).ok_or(ImageError::BadPpm("truncated header"))?;
+        *field = std::str::from_utf8(token)
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or(ImageError::BadPpm("header field is not a number"))?;
+    }
+    let [width, height, max] = header;
+    if max == 0 || max > 65535 {
+        return Err(ImageError::BadPpm("max value out of range"));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or(ImageError::BadPpm("image too large"))?;
+    let samples: Vec<usize> = if binary {
+        // Exactly one whitespace byte separates the header from the raster.
+        let raster = bytes.get(pos + 1..).unwrap_or_default();
+        let wide = max > 255;
+        let size = if wide { 2 } else { 1 };
+        if raster.len() / size < count {
+            return Err(ImageError::BadPpm("truncated raster"));
+        }
+        raster
+            .chunks_exact(size)
+            .take(count)
+            .map(|s| {
+                if wide {
+                    u16::from_be_bytes([s[0], s[1]]) as usize
+                } else {
+                    s[0] as usize
+                }
+            })
+            .collect()
+    } else {
+        // Every sample takes at least a byte, so the header can't ask for more than are left.
+        if bytes.len() - pos < count {
+            return Err(ImageError::BadPpm("truncated raster"));
+        }
+        let mut samples = Vec::with_capacity(count);
+        while samples.len() < count {
+            let token =
+                next_token(&bytes, &mut pos).ok_or(ImageError::BadPpm("truncated raster"))?;
+            let sample = std::str::from_utf8(token)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or(ImageError::BadPpm("sample is not a number"))?;
+            samples.push(sample);
+        }
+        samples
+    };
+
+    let to_byte = |s: usize| (s.min(max) * 255 / max) as u8;
+    let cells = samples
+        .chunks_exact(3)
+        .map(|px| pack_rgba(to_byte(px[0]), to_byte(px[1]), to_byte(px[2]), 255))
+        .collect();
+    Ok(Grid::from_vec(width, height, cells)?)
+}
+
+/// Writes a binary (P6) PPM. Alpha is dropped.
+pub fn write_ppm<C, B, F, W>(
+    grid: &B,
+    mut writer: W,
+    scale: usize,
+    colour: F,
+) -> Result<(), ImageError>
+where
+    C: Copy,
+    B: TwoDeeRead<C>,
+    F: Fn(C) -> u32,
+    W: Write,
+{
+    let (width, height, pixels) = scaled_pixels(grid, scale, colour)?;
+    write!(writer, "P6\n{width} {height}\n255\n")?;
+    let bytes: Vec<u8> = pixels
+        .iter()
+        .flat_map(|c| {
+            let [r, g, b, _] = unpack_rgba(*c);
+            [r, g, b]
+        })
+        .collect();
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Next whitespace separated token of a PPM header, skipping `#` comments.
+fn next_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while bytes.get(*pos)?.is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if bytes[*pos] != b'#' {
+            break;
+        }
+        while *bytes.get(*pos)? != b'\n' {
+            *pos += 1;
+        }
+    }
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    Some(&bytes[start..*pos])
+}
+
+fn too_large<C: Copy, B: TwoDeeRead<C>>(grid: &B, scale: usize) -> ImageError {
+    ImageError::TooLarge {
+        width: grid.width(),
+        height: grid.height(),
+        scale,
+    }
+}
+
+/// Colours every cell and blows each up into a `scale` x `scale` block.
+fn scaled_pixels<C, B, F>(
+    grid: &B,
+    scale: usize,
+    colour: F,
+) -> Result<(usize, usize, Vec<u32>), ImageError>
+where
+    C: Copy,
+    B: TwoDeeRead<C>,
+    F: Fn(C) -> u32,
+{
+    let scale = scale.max(1);
+    let (width, height) = grid
+        .width()
+        .checked_mul(scale)
+        .zip(grid.height().checked_mul(scale))
+        .filter(|(w, h)| w.checked_mul(*h).is_some())
+        .ok_or_else(|| too_large(grid, scale))?;
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in grid.rows() {
+        let line: Vec<u32> = row
+            .iter()
+            .flat_map(|c| std::iter::repeat_n(colour(*c), scale))
+            .collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Colormap;
+
+    fn sample() -> Grid<u32> {
+        let colours = vec![
+            pack_rgba(255, 0, 0, 255),
+            pack_rgba(0, 255, 0, 255),
+            pack_rgba(0, 0, 255, 255),
+            pack_rgba(10, 20, 30, 255),
+        ];
+        Grid::from_vec(2, 2, colours).unwrap()
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let mut bytes = vec![];
+        write_png(&sample(), &mut bytes, 1, |c| c).unwrap();
+        assert_eq!(read_png(bytes.as_slice()).unwrap(), sample());
+    }
+
+    #[test]
+    fn ppm_round_trip_scaled() {
+        let mut bytes = vec![];
+        write_ppm(&sample(), &mut bytes, 3, |c| c).unwrap();
+        let scaled = read_ppm(bytes.as_slice()).unwrap();
+        assert_eq!((scaled.width(), scaled.height()), (6, 6));
+        assert_eq!(scaled.get(5, 5), sample().get(1, 1));
+        assert_eq!(scaled.get(2, 0), sample().get(0, 0));
+    }
+
+    #[test]
+    fn plain_ppm_with_comments() {
+        let text = "P3\n# made by hand\n2 1\n15\n15 0 0  0 0 15\n";
+        let grid = read_ppm(text.as_bytes()).unwrap();
+        assert_eq!(
+            grid.row(0),
+            &[pack_rgba(255, 0, 0, 255), pack_rgba(0, 0, 255, 255)]
+        );
+        assert!(read_ppm("P6\n2 2\n255\n\0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn ppm_header_sizes_checked() {
+        let huge = format!("P6\n{} {}\n255\n\0\0\0", usize::MAX / 2, 3);
+        assert!(matches!(
+            read_ppm(huge.as_bytes()),
+            Err(ImageError::BadPpm("image too large"))
+        ));
+        // Big enough to abort if it were allocated up front.
+        let plain = "P3\n1000000000 1000000\n255\n0 0 0\n";
+        assert!(matches!(
+            read_ppm(plain.as_bytes()),
+            Err(ImageError::BadPpm("truncated raster"))
+        ));
+    }
+
+    #[test]
+    fn oversized_output_rejected() {
+        let too_large = |r: Result<(), ImageError>| matches!(r, Err(ImageError::TooLarge { .. }));
+        assert!(too_large(write_ppm(&sample(), vec![], usize::MAX, |c| c)));
+        assert!(too_large(write_png(&sample(), vec![], usize::MAX, |c| c)));
+        // Fits in memory, as it has no rows, but not in a PNG header.
+        let empty = Grid::new(1, 0, 0u32);
+        assert!(too_large(write_png(&empty, vec![], usize::MAX / 2, |c| c)));
+    }
+
+    #[test]
+    fn scalar_grid_through_colormap() {
+        let heights = Grid::from_vec(2, 1, vec![0.0f32, 1.0]).unwrap();
+        let mut bytes = vec![];
+        write_png(
+            &heights,
+            &mut bytes,
+            1,
+            Colormap::Greyscale.scaled(0.0, 1.0),
+        )
+        .unwrap();
+        let grid = read_png(bytes.as_slice()).unwrap();
+        assert_eq!(
+            grid.row(0),
+            &[pack_rgba(0, 0, 0, 255), pack_rgba(255, 255, 255, 255)]
+        );
+    }
+}
@@ -1,9 +1,59 @@
+mod automata;
 mod buf;
+mod cell;
+mod colour;
+mod diff;
+mod diffusion;
+mod distance;
+mod draw;
 mod fixed_buf;
+mod history;
+mod image;
+mod life;
+mod maze;
+mod neighbours;
+mod path;
+mod rect;
+mod regions;
+mod replay;
+mod rle;
+mod sand;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod snapshot;
+mod text;
+mod toroidal;
+mod transform;
+mod turmite;
+mod view;
+pub use automata::*;
 pub use buf::*;
+pub use cell::*;
+pub use colour::*;
+pub use diff::*;
+pub use diffusion::*;
+pub use distance::*;
+pub use draw::*;
 pub use fixed_buf::*;
+pub use history::*;
+pub use image::*;
+pub use life::*;
+pub use maze::*;
+pub use neighbours::*;
+pub use path::*;
+pub use rect::*;
+pub use regions::*;
+pub use replay::*;
+pub use rle::*;
+pub use sand::*;
+pub use snapshot::*;
+pub use text::*;
+pub use toroidal::*;
+pub use transform::*;
+pub use turmite::*;
+pub use view::*;
 
-pub type MyBuf = DoubleBuf<50, 50>;
+pub type MyBuf = DoubleBuf<u32, 50, 50>;
 
 mod gfx;
 pub use gfx::*;
@@ -13,6 +63,7 @@ pub fn pack_rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
     ((a as u32) << 24) | ((b as u32) << 16) | ((g as u32) << 8) | (r as u32)
 }
 
+#[deprecated(note = "use pack_rgba; the viewer now decodes RGBA8")]
 pub fn mak_coolor(r: u8, g: u8, b: u8) -> u32 {
     let r5 = ((r as u32) >> 3) & 0x1F;
     let g5 = ((g as u32) >> 3) & 0x1F;
@@ -0,0 +1,268 @@
+//! Life-like cellular automata: two-state cells on the Moore neighbourhood, with births and
+//! survivals picked by a `B3/S23` style rule.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use thiserror::Error;
+
+use crate::automata::{step_by_count, Automaton};
+use crate::buf::{BufferError, TwoDeeBuffer, TwoDeeRead};
+use crate::cell::BinaryCell;
+use crate::colour::Palette;
+use crate::neighbours::Edge;
+use crate::pack_rgba;
+use crate::rle::Pattern;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RuleError {
+    #[error("Malformed rule string: {0}")]
+    Malformed(String),
+    #[error("Neighbour count {0:?} out of range")]
+    BadCount(char),
+    #[error("Turmite rules need 1 to 256 colours, not {0}")]
+    BadColours(usize),
+    #[error("Turmite table is ragged or refers to missing colours or states")]
+    BadTable,
+}
+
+/// Which neighbour counts give birth to a dead cell and which keep a live one alive, as bit
+/// masks over 0..=8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LifeRule {
+    pub birth: u16,
+    pub survival: u16,
+}
+
+const fn counts(digits: &[u8]) -> u16 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        mask |= 1 << digits[i];
+        i += 1;
+    }
+    mask
+}
+
+impl LifeRule {
+    /// B3/S23
+    pub const CONWAY: Self = Self::new(counts(&[3]), counts(&[2, 3]));
+    /// B36/S23, Conway plus a replicator.
+    pub const HIGHLIFE: Self = Self::new(counts(&[3, 6]), counts(&[2, 3]));
+    /// B2/S, every cell dies each step.
+    pub const SEEDS: Self = Self::new(counts(&[2]), 0);
+    /// B3678/S34678, symmetric under swapping live and dead.
+    pub const DAY_AND_NIGHT: Self = Self::new(counts(&[3, 6, 7, 8]), counts(&[3, 4, 6, 7, 8]));
+
+    pub const fn new(birth: u16, survival: u16) -> Self {
+        Self { birth, survival }
+    }
+
+    /// Reads `B3/S23` (either order, any case), or the older survival-first `23/3`.
+    pub fn parse(rule: &str) -> Result<Self, RuleError> {
+        let malformed = || RuleError::Malformed(rule.to_string());
+        let (first, second) = rule.trim().split_once('/').ok_or_else(malformed)?;
+        let tag = |part: &str| part.chars().next().map(|c| c.to_ascii_uppercase());
+        let (birth, survival) = match (tag(first), tag(second)) {
+            (Some('B'), Some('S')) => (&first[1..], &second[1..]),
+            (Some('S'), Some('B')) => (&second[1..], &first[1..]),
+            (a, b) if [a, b].iter().all(|t| t.is_none_or(|c| c.is_ascii_digit())) => {
+                (second, first)
+            }
+            _ => return Err(malformed()),
+        };
+        Ok(Self::new(parse_counts(birth)?, parse_counts(survival)?))
+    }
+
+    pub fn born(&self, neighbours: usize) -> bool {
+        neighbours <= 8 && self.birth & (1 << neighbours) != 0
+    }
+
+    pub fn survives(&self, neighbours: usize) -> bool {
+        neighbours <= 8 && self.survival & (1 << neighbours) != 0
+    }
+
+    /// Whether a cell is alive next step.
+    pub fn next(&self, alive: bool, neighbours: usize) -> bool {
+        if alive {
+            self.survives(neighbours)
+        } else {
+            self.born(neighbours)
+        }
+    }
+}
+
+/// Digits 0..=8 as a count mask.
+pub(crate) fn parse_counts(digits: &str) -> Result<u16, RuleError> {
+    digits.chars().try_fold(0, |mask, c| match c.to_digit(10) {
+        Some(n @ 0..=8) => Ok(mask | 1 << n),
+        _ => Err(RuleError::BadCount(c)),
+    })
+}
+
+impl FromStr for LifeRule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LifeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = |mask: u16| -> String {
+            (0..=8)
+                .filter(|n| mask & (1 << n) != 0)
+                .map(|n| char::from(b'0' + n as u8))
+                .collect()
+        };
+        write!(f, "B{}/S{}", digits(self.birth), digits(self.survival))
+    }
+}
+
+impl Default for LifeRule {
+    fn default() -> Self {
+        Self::CONWAY
+    }
+}
+
+/// A Life-like automaton over any grid of `BinaryCell`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LifeLike<C = bool> {
+    pub rule: LifeRule,
+    /// How neighbours past the border are found. `Edge::Constant` sets what the outside
+    /// counts as.
+    pub edge: Edge<C>,
+}
+
+impl<C: BinaryCell> LifeLike<C> {
+    /// `rule` on a wrapping (toroidal) world.
+    pub fn new(rule: LifeRule) -> Self {
+        Self {
+            rule,
+            edge: Edge::Wrap,
+        }
+    }
+
+    pub fn with_edge(mut self, edge: Edge<C>) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Uses the pattern's own rule, or Conway's if it doesn't name one.
+    pub fn for_pattern(pattern: &Pattern) -> Result<Self, RuleError> {
+        let rule = match &pattern.rule {
+            Some(rule) => LifeRule::parse(rule)?,
+            None => LifeRule::CONWAY,
+        };
+        Ok(Self::new(rule))
+    }
+}
+
+impl<C: BinaryCell> Automaton<C> for LifeLike<C> {
+    fn step<S, D>(&self, src: &S, dst: &mut D) -> Result<(), BufferError>
+    where
+        S: TwoDeeRead<C> + ?Sized,
+        D: TwoDeeBuffer<C> + ?Sized,
+    {
+        step_by_count(src, dst, self.edge, C::is_alive, |cell, live| {
+            C::from_alive(self.rule.next(cell.is_alive(), live))
+        })
+    }
+
+    /// Dead black, alive white.
+    fn palette(&self) -> Palette {
+        Palette::new(vec![pack_rgba(0, 0, 0, 255), pack_rgba(255, 255, 255, 255)])
+    }
+}
+
+impl<C: BinaryCell> Default for LifeLike<C> {
+    fn default() -> Self {
+        Self::new(LifeRule::CONWAY)
+    }
+}
+
+/// Sets each cell alive with probability `density`.
+pub fn seed_random<C, B, R>(grid: &mut B, density: f64, rng: &mut R)
+where
+    C: BinaryCell,
+    B: TwoDeeBuffer<C> + ?Sized,
+    R: Rng + ?Sized,
+{
+    let density = density.clamp(0.0, 1.0);
+    for row in grid.rows_mut() {
+        for cell in row {
+            *cell = C::from_alive(rng.gen_bool(density));
+        }
+    }
+}
+
+/// Number of live cells.
+pub fn population<C: BinaryCell, B: TwoDeeRead<C> + ?Sized>(grid: &B) -> usize {
+    grid.rows()
+        .map(|row| row.iter().filter(|c| c.is_alive()).count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DoubleBuf, Grid, ToText};
+    use rand::prelude::*;
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!("B3/S23".parse(), Ok(LifeRule::CONWAY));
+        assert_eq!(LifeRule::parse("s23/b36"), Ok(LifeRule::HIGHLIFE));
+        assert_eq!(LifeRule::parse("23/3"), Ok(LifeRule::CONWAY));
+        assert_eq!(LifeRule::parse("B2/S"), Ok(LifeRule::SEEDS));
+        assert_eq!(LifeRule::DAY_AND_NIGHT.to_string(), "B3678/S34678");
+        assert_eq!(LifeRule::parse("B9/S1"), Err(RuleError::BadCount('9')));
+        assert_eq!(
+            LifeRule::parse("B3S23"),
+            Err(RuleError::Malformed("B3S23".into()))
+        );
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let start = Grid::parse(".....\n..#..\n..#..\n..#..\n.....", |c| c == '#').unwrap();
+        let life = LifeLike::default().with_edge(Edge::Skip);
+        let mut next = start.clone();
+        life.step(&start, &mut next).unwrap();
+        let show = |g: &Grid<bool>| g.to_text(|c| if c { '#' } else { '.' });
+        assert_eq!(show(&next), ".....\n.....\n.###.\n.....\n.....\n");
+        let mut again = start.clone();
+        life.step(&next, &mut again).unwrap();
+        assert_eq!(again, start);
+    }
+
+    #[test]
+    fn glider_wraps_through_double_buffer() {
+        let glider = Pattern::parse("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+        let life = LifeLike::<u8>::for_pattern(&glider).unwrap();
+        let mut buf = DoubleBuf::<u8, 6, 6>::new();
+        buf.update(|f| glider.place(f, (0, 0)));
+        // A glider moves one cell diagonally every four generations.
+        for _ in 0..24 {
+            life.tick(&mut buf);
+        }
+        buf.render(|f| {
+            assert_eq!(population(f), 5);
+            assert_eq!(Pattern::from_grid(f).cells, {
+                let mut shifted = Grid::new(6, 6, false);
+                glider.place(&mut shifted, (0, 0));
+                shifted
+            });
+        });
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut grid = Grid::new(10, 10, false);
+        seed_random(&mut grid, 0.3, &mut rng);
+        assert!((10..=50).contains(&population(&grid)));
+    }
+}
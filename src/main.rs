@@ -1,9 +1,19 @@
 use std::time::Duration;
 
-use sim_test::{pack_rgba, App};
+use rand::prelude::*;
+use sim_test::{
+    label_components, lerp_rgba, pack_rgba, seed_random, App, Automaton, Boundary, Colormap,
+    Connectivity, DiffOverlay, Diffusion, DistanceField, DoubleBuf, Draw, FallingSand, FrameSource,
+    Generations, Grid, Heading, HeatSource, Heuristic, HistoryBuf, LifeLike, LifeRule, Mark,
+    Material, MazeAlgorithm, MazeGenerator, Metric, Palette, PathAlgorithm, Pathfinder, Player,
+    Recorder, Rect, Scheme, TurmiteRule, Turmites, Wireworld,
+};
+use tracing::{error, info};
 use winit::event_loop::{ControlFlow, EventLoop};
 
-use sim_test::{MyBuf, TwoDeeBuffer};
+use sim_test::{MyBuf, TwoDeeBuffer, TwoDeeRead};
+
+const TICK: Duration = Duration::from_millis(60);
 
 #[pollster::main]
 async fn main() {
@@ -11,21 +21,334 @@ async fn main() {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let buf = MyBuf::new();
-
-    let mut buf2 = buf.clone();
-    std::thread::spawn(move || {
-        let mut r: u8 = 0;
-        loop {
-            r = r.saturating_add(1);
-            //println!("r is now {}", r);
-            buf2.update(|f| {
-                f.set(0, 0, pack_rgba(r, 0, 0, 255)).unwrap();
+    // `sim-test record <log> [seed]` saves a run, `sim-test replay <log>` plays one back,
+    // `sim-test life [rule]` runs a Life-like automaton, `generations [rule]`, `wireworld` and
+    // `ant [rule]` the multi-state ones, `sand` pours sand and water over some ledges, and
+    // `maze [algorithm] [seed]` draws a maze step by step, `path [search]` solves one,
+    // `regions` colours the connected blobs of a random image, and `voronoi [metric]` shows the
+    // nearest of some wandering seeds, and `heat [scheme]` spreads heat around a room.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [mode, path, rest @ ..] if mode == "record" => {
+            let seed = rest
+                .first()
+                .map_or(0, |s| s.parse().expect("seed is a number"));
+            let buf = MyBuf::new();
+            let mut recorder = Recorder::create(buf.clone(), seed, path).expect("create log");
+            std::thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(seed);
+                loop {
+                    let logged = recorder.update(|f| {
+                        let (x, y) = (rng.gen_range(0..50), rng.gen_range(0..50));
+                        f.set(x, y, pack_rgba(rng.gen(), rng.gen(), rng.gen(), 255))
+                            .unwrap();
+                    });
+                    // The window closing kills this thread, so every tick is flushed as it goes.
+                    if let Err(e) = logged.and_then(|()| recorder.flush()) {
+                        error!("Recording stopped at tick {}: {e}", recorder.tick());
+                        return;
+                    }
+                    std::thread::sleep(TICK);
+                }
+            });
+            run(event_loop, buf);
+        }
+        [mode, path] if mode == "replay" => {
+            let mut buf = MyBuf::new();
+            let opened = Player::open(path);
+            let mut player = match opened.and_then(|p| p.publish(&mut buf).map(|()| p)) {
+                Ok(player) => player,
+                Err(e) => {
+                    error!("Can't replay {path}: {e}");
+                    return;
+                }
+            };
+            let mut buf2 = buf.clone();
+            std::thread::spawn(move || loop {
+                match player.step() {
+                    Ok(Some(_)) => player.publish(&mut buf2).expect("size checked on open"),
+                    Ok(None) => {
+                        info!("Replay finished at tick {}", player.tick());
+                        return;
+                    }
+                    Err(e) => {
+                        error!("Replay stopped at tick {}: {e}", player.tick());
+                        return;
+                    }
+                }
+                std::thread::sleep(TICK);
+            });
+            run(event_loop, buf);
+        }
+        [mode, rest @ ..] if mode == "life" => {
+            let rule = rest.first().map_or(Ok(LifeRule::CONWAY), |r| r.parse());
+            let life = LifeLike::new(rule.expect("valid rule"));
+            let mut buf = HistoryBuf::new(DoubleBuf::<bool, 100, 100>::new(), 256);
+            buf.update(|f| seed_random(f, 0.3, &mut thread_rng()));
+            run_automaton(event_loop, buf, life);
+        }
+        [mode, rest @ ..] if mode == "generations" => {
+            let rule = rest
+                .first()
+                .map_or(Ok(Generations::BRIANS_BRAIN), |r| r.parse());
+            let rule = rule.expect("valid rule");
+            let mut buf = HistoryBuf::new(DoubleBuf::<u8, 100, 100>::new(), 256);
+            buf.update(|f| seed_random(f, 0.2, &mut thread_rng()));
+            run_automaton(event_loop, buf, rule);
+        }
+        [mode] if mode == "wireworld" => {
+            // Two clocks of different periods sending electrons down wires.
+            let circuit = Grid::try_parse(WIREWORLD_DEMO, Wireworld::parse_state).unwrap();
+            let mut buf = HistoryBuf::new(DoubleBuf::<u8, 40, 20>::new(), 256);
+            buf.update(|f| {
+                f.blit(
+                    &circuit,
+                    Rect::of_size(circuit.width(), circuit.height()),
+                    (2, 2),
+                );
+            });
+            run_automaton(event_loop, buf, Wireworld::default());
+        }
+        [mode, rest @ ..] if mode == "ant" => {
+            let rule = rest
+                .first()
+                .map_or(Ok(TurmiteRule::langton()), |r| r.parse());
+            let mut ants =
+                Turmites::new(rule.expect("valid rule")).with_ant(50, 50, Heading::North);
+            let palette = ants.palette();
+            let buf = HistoryBuf::new(DoubleBuf::<u8, 100, 100>::new(), 256);
+            let mut sim = buf.clone();
+            std::thread::spawn(move || loop {
+                for _ in 0..10 {
+                    ants.tick(&mut sim);
+                }
+                std::thread::sleep(TICK);
+            });
+            run(event_loop, buf.coloured(move |c| palette.colour(c)));
+        }
+        [mode, rest @ ..] if mode == "maze" => {
+            let algorithm = rest
+                .first()
+                .map_or(Some(MazeAlgorithm::RecursiveBacktracker), |a| {
+                    MazeAlgorithm::from_name(a)
+                });
+            let seed = rest.get(1).map_or_else(
+                || thread_rng().gen(),
+                |s| s.parse().expect("seed is a number"),
+            );
+            let maze = MazeGenerator::new(algorithm.expect("known maze algorithm"), seed);
+            let buf = HistoryBuf::new(DoubleBuf::<bool, 81, 61>::new(), 4096);
+            let mut sim = buf.clone();
+            std::thread::spawn(move || {
+                let mut grid = Grid::new(81, 61, false);
+                maze.generate_with(&mut grid, false, true, |g| {
+                    sim.update(|f| f.copy_from(g).unwrap());
+                    std::thread::sleep(TICK / 10);
+                });
+            });
+            let (wall, open) = (pack_rgba(0, 0, 0, 255), pack_rgba(255, 255, 255, 255));
+            run(
+                event_loop,
+                buf.coloured(move |c| if c { open } else { wall }),
+            );
+        }
+        [mode, rest @ ..] if mode == "path" => {
+            let algorithm = match rest.first().map(String::as_str) {
+                None | Some("astar") => PathAlgorithm::AStar(Heuristic::Manhattan),
+                Some("bfs") => PathAlgorithm::Bfs,
+                Some("dijkstra") => PathAlgorithm::Dijkstra,
+                Some("bidirectional") => PathAlgorithm::BidirectionalBfs,
+                Some(other) => panic!("unknown search {other}"),
+            };
+            // A maze with some extra holes knocked in it, so there's more than one way through.
+            let mut rng = thread_rng();
+            let mut maze = Grid::new(81, 61, false);
+            MazeGenerator::new(MazeAlgorithm::Kruskal, rng.gen()).generate(&mut maze, false, true);
+            for _ in 0..150 {
+                let (x, y) = (rng.gen_range(1..80), rng.gen_range(1..60));
+                maze.set(x, y, true).unwrap();
+            }
+            let colours = [
+                pack_rgba(40, 40, 40, 255),
+                pack_rgba(255, 200, 0, 255),
+                pack_rgba(60, 90, 200, 255),
+                pack_rgba(255, 40, 40, 255),
+            ];
+            let paint = move |open: bool, mark: Mark| {
+                if open {
+                    colours[mark as usize]
+                } else {
+                    pack_rgba(0, 0, 0, 255)
+                }
+            };
+            let buf = HistoryBuf::new(DoubleBuf::<u32, 81, 61>::new(), 4096);
+            let mut sim = buf.clone();
+            std::thread::spawn(move || {
+                let finder = Pathfinder::new(|open: &bool| open.then_some(1.0));
+                let show = |sim: &mut HistoryBuf<u32, 81, 61>, marks: &Grid<Mark>| {
+                    sim.update(|f| {
+                        for ((x, y), px) in f.iter_mut() {
+                            *px = paint(maze.get(x, y).unwrap(), marks.get(x, y).unwrap());
+                        }
+                    });
+                };
+                let search =
+                    finder.search_with(&maze, (1, 1), Some((79, 59)), algorithm, |marks| {
+                        show(&mut sim, marks);
+                        std::thread::sleep(TICK / 20);
+                    });
+                show(&mut sim, &search.marks);
             });
-            std::thread::sleep(Duration::from_millis(60));
+            run(event_loop, buf);
         }
+        [mode] if mode == "regions" => {
+            // Random blobs, each connected one in its own colour.
+            let mut soup = Grid::new(100, 100, false);
+            seed_random(&mut soup, 0.45, &mut thread_rng());
+            let regions = label_components(&soup, Connectivity::Four, |c| *c);
+            let palette = regions.palette(thread_rng().gen());
+            let mut buf = DoubleBuf::<usize, 100, 100>::new();
+            buf.update(|f| f.copy_from(&regions.labels).unwrap());
+            run(event_loop, buf.coloured(move |c| palette.colour(c)));
+        }
+        [mode, rest @ ..] if mode == "voronoi" => {
+            let metric = match rest.first().map(String::as_str) {
+                None | Some("euclidean") => Metric::Euclidean,
+                Some("manhattan") => Metric::Manhattan,
+                Some("chebyshev") => Metric::Chebyshev,
+                Some(other) => panic!("unknown metric {other}"),
+            };
+            // Seeds wandering about, with each cell in its nearest seed's colour, darker the
+            // further away it is.
+            let palette = Palette::random(21, thread_rng().gen());
+            let buf = HistoryBuf::new(DoubleBuf::<u32, 100, 100>::new(), 256);
+            let mut sim = buf.clone();
+            std::thread::spawn(move || {
+                let mut rng = thread_rng();
+                let mut field = DistanceField::new(100, 100, metric);
+                let mut seeds: Vec<(usize, usize)> = (0..20)
+                    .map(|_| (rng.gen_range(0..100), rng.gen_range(0..100)))
+                    .collect();
+                loop {
+                    for (x, y) in &mut seeds {
+                        *x = x.saturating_add_signed(rng.gen_range(-1..=1)).min(99);
+                        *y = y.saturating_add_signed(rng.gen_range(-1..=1)).min(99);
+                    }
+                    field.compute(&seeds);
+                    sim.update(|f| {
+                        for ((x, y), px) in f.iter_mut() {
+                            let colour = palette.colour(field.nearest.get(x, y).unwrap());
+                            let d = field.distances.get(x, y).unwrap();
+                            *px = lerp_rgba(colour, pack_rgba(0, 0, 0, 255), d / 40.0);
+                        }
+                    });
+                    std::thread::sleep(TICK);
+                }
+            });
+            run(event_loop, buf);
+        }
+        [mode, rest @ ..] if mode == "heat" => {
+            let scheme = match rest.first().map(String::as_str) {
+                None | Some("explicit") => Scheme::Explicit,
+                Some("jacobi") => Scheme::Jacobi(20),
+                Some("gauss-seidel") => Scheme::GaussSeidel(10),
+                Some(other) => panic!("unknown scheme {other}"),
+            };
+            // A hot plate and a heater warming a room with cold walls.
+            let mut heat = Diffusion::new(1.0, 0.2)
+                .with_boundary(Boundary::Dirichlet(0.0))
+                .with_scheme(scheme)
+                .with_source(HeatSource::Rate {
+                    x: 70,
+                    y: 60,
+                    rate: 40.0,
+                });
+            for x in 20..40 {
+                heat = heat.with_source(HeatSource::Fixed {
+                    x,
+                    y: 30,
+                    value: 1.0,
+                });
+            }
+            let buf = HistoryBuf::new(DoubleBuf::<f32, 100, 100>::new(), 256);
+            let mut sim = buf.clone();
+            std::thread::spawn(move || loop {
+                for _ in 0..5 {
+                    heat.tick(&mut sim);
+                }
+                std::thread::sleep(TICK);
+            });
+            run(event_loop, buf.coloured(Colormap::Heat.scaled(0.0, 1.0)));
+        }
+        [mode] if mode == "sand" => {
+            let mut sand = FallingSand::new(thread_rng().gen());
+            let buf = HistoryBuf::new(DoubleBuf::<Material, 100, 100>::new(), 256);
+            let mut sim = buf.clone();
+            sim.update(|f| {
+                f.draw_line((20, 60), (45, 70), Material::Stone);
+                f.draw_line((80, 40), (55, 50), Material::Stone);
+                f.draw_line((0, 99), (99, 99), Material::Stone);
+            });
+            std::thread::spawn(move || {
+                let mut rng = thread_rng();
+                loop {
+                    sim.update(|f| {
+                        f.set(rng.gen_range(30..40), 0, Material::Sand).unwrap();
+                        f.set(rng.gen_range(60..70), 0, Material::Water).unwrap();
+                    });
+                    sand.tick(&mut sim);
+                    std::thread::sleep(TICK / 2);
+                }
+            });
+            let palette = FallingSand::palette();
+            run(event_loop, buf.coloured(move |c| palette.colour(c)));
+        }
+        _ => {
+            let buf = HistoryBuf::new(MyBuf::new(), 256);
+            let mut buf2 = buf.clone();
+            std::thread::spawn(move || {
+                let mut r: u8 = 0;
+                loop {
+                    r = r.saturating_add(1);
+                    //println!("r is now {}", r);
+                    buf2.update(|f| {
+                        f.set(0, 0, pack_rgba(r, 0, 0, 255)).unwrap();
+                    });
+                    std::thread::sleep(TICK);
+                }
+            });
+            run(event_loop, buf);
+        }
+    }
+}
+
+const WIREWORLD_DEMO: &str = "\
+.tH..........................\n\
+#..#########################.\n\
+.##..........................\n\
+.............................\n\
+.tH#.........................\n\
+#...#########################\n\
+.###.........................";
+
+fn run_automaton<C, A, const W: usize, const H: usize>(
+    event_loop: EventLoop<()>,
+    buf: HistoryBuf<C, W, H>,
+    rule: A,
+) where
+    C: Copy + PartialEq + Into<usize> + Send + Sync + 'static,
+    A: Automaton<C> + Send + 'static,
+{
+    let palette = rule.palette();
+    let mut sim = buf.clone();
+    std::thread::spawn(move || loop {
+        rule.tick(&mut sim);
+        std::thread::sleep(TICK);
     });
+    run(event_loop, buf.coloured(move |c| palette.colour(c)));
+}
 
-    let mut app = App::new(buf);
+fn run<S: FrameSource>(event_loop: EventLoop<()>, buf: S) {
+    let mut app = App::new(buf).with_diff_overlay(DiffOverlay::default());
     event_loop.run_app(&mut app).expect("idk");
 }
@@ -0,0 +1,460 @@
+//! Perfect maze generators: every cell reachable from every other by exactly one path.
+//!
+//! Mazes are drawn onto a grid with walls between cells, so a maze of `w` by `h` cells
+//! takes a `2w + 1` by `2h + 1` grid. Cell (x, y) sits at (2x + 1, 2y + 1), and the wall
+//! between two cells is the grid cell halfway between them. Even sized grids leave an extra
+//! row or column of wall on the right and bottom.
+
+use rand::prelude::*;
+
+use crate::buf::{Grid, Publisher, TwoDeeBuffer, TwoDeeRead};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MazeAlgorithm {
+    /// Depth-first random walk, backing up at dead ends. Long winding corridors.
+    RecursiveBacktracker,
+    /// Grows the maze from a random frontier cell at a time. Lots of short dead ends.
+    Prim,
+    /// Knocks down walls in random order wherever they join two separate regions.
+    Kruskal,
+    /// Loop-erased random walks, giving an unbiased pick of all possible mazes.
+    Wilson,
+    /// Builds a row at a time, only ever remembering the current row.
+    Eller,
+    /// Starts open and adds walls with a single gap, splitting the space in two each time.
+    RecursiveDivision,
+}
+
+impl MazeAlgorithm {
+    pub const ALL: [MazeAlgorithm; 6] = [
+        MazeAlgorithm::RecursiveBacktracker,
+        MazeAlgorithm::Prim,
+        MazeAlgorithm::Kruskal,
+        MazeAlgorithm::Wilson,
+        MazeAlgorithm::Eller,
+        MazeAlgorithm::RecursiveDivision,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MazeAlgorithm::RecursiveBacktracker => "backtracker",
+            MazeAlgorithm::Prim => "prim",
+            MazeAlgorithm::Kruskal => "kruskal",
+            MazeAlgorithm::Wilson => "wilson",
+            MazeAlgorithm::Eller => "eller",
+            MazeAlgorithm::RecursiveDivision => "division",
+        }
+    }
+
+    /// Looks an algorithm up by `name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// One of the algorithms plus the seed it draws from, so a generator always makes the same
+/// maze for the same grid size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MazeGenerator {
+    pub algorithm: MazeAlgorithm,
+    pub seed: u64,
+}
+
+impl MazeGenerator {
+    pub fn new(algorithm: MazeAlgorithm, seed: u64) -> Self {
+        Self { algorithm, seed }
+    }
+
+    /// Draws a maze over the whole of `grid`, with `wall` and `open` cells.
+    pub fn generate<C, B>(&self, grid: &mut B, wall: C, open: C)
+    where
+        C: Copy,
+        B: TwoDeeBuffer<C> + ?Sized,
+    {
+        self.generate_with(grid, wall, open, |_| {});
+    }
+
+    /// As `generate`, calling `on_step` with the grid after every passage carved (or wall
+    /// added, for recursive division).
+    pub fn generate_with<C, B, F>(&self, grid: &mut B, wall: C, open: C, on_step: F)
+    where
+        C: Copy,
+        B: TwoDeeBuffer<C> + ?Sized,
+        F: FnMut(&B),
+    {
+        let mut carver = Carver {
+            w: grid.width().saturating_sub(1) / 2,
+            h: grid.height().saturating_sub(1) / 2,
+            grid,
+            wall,
+            open,
+            rng: StdRng::seed_from_u64(self.seed),
+            on_step,
+        };
+        carver.grid.fill(wall);
+        if carver.w == 0 || carver.h == 0 {
+            return;
+        }
+        match self.algorithm {
+            MazeAlgorithm::RecursiveBacktracker => carver.backtracker(),
+            MazeAlgorithm::Prim => carver.prim(),
+            MazeAlgorithm::Kruskal => carver.kruskal(),
+            MazeAlgorithm::Wilson => carver.wilson(),
+            MazeAlgorithm::Eller => carver.eller(),
+            MazeAlgorithm::RecursiveDivision => carver.division(),
+        }
+    }
+
+    /// Generates a maze the size of `buf`'s frames, publishing a frame after every step so a
+    /// viewer shows it being built.
+    pub fn animate<C, P>(&self, buf: &mut P, wall: C, open: C)
+    where
+        C: Copy,
+        P: Publisher<C>,
+    {
+        let mut size = (0, 0);
+        buf.publish(|f| {
+            size = (f.width(), f.height());
+            f.fill(wall);
+        });
+        let mut maze = Grid::new(size.0, size.1, wall);
+        self.generate_with(&mut maze, wall, open, |m| {
+            buf.publish(|f| f.copy_from(m).expect("frames are all the same size"))
+        });
+    }
+}
+
+/// The state shared by every algorithm. Coordinates are in cells, not grid cells.
+struct Carver<'a, C, B: ?Sized, R, F> {
+    grid: &'a mut B,
+    w: usize,
+    h: usize,
+    wall: C,
+    open: C,
+    rng: R,
+    on_step: F,
+}
+
+impl<C, B, R, F> Carver<'_, C, B, R, F>
+where
+    C: Copy,
+    B: TwoDeeBuffer<C> + ?Sized,
+    R: Rng,
+    F: FnMut(&B),
+{
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        y * self.w + x
+    }
+
+    fn cell(&self, i: usize) -> (usize, usize) {
+        (i % self.w, i / self.w)
+    }
+
+    fn neighbours(&self, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+        let (w, h) = (self.w, self.h);
+        [(0, -1), (1, 0), (0, 1), (-1, 0)]
+            .into_iter()
+            .filter_map(move |(dx, dy)| {
+                let (nx, ny) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+                (nx < w && ny < h).then_some((nx, ny))
+            })
+    }
+
+    fn random_cell(&mut self) -> (usize, usize) {
+        (self.rng.gen_range(0..self.w), self.rng.gen_range(0..self.h))
+    }
+
+    fn open_cell(&mut self, (x, y): (usize, usize)) {
+        self.grid.row_mut(2 * y + 1)[2 * x + 1] = self.open;
+    }
+
+    /// Opens both cells and the wall between them.
+    fn join(&mut self, a: (usize, usize), b: (usize, usize)) {
+        self.open_cell(a);
+        self.open_cell(b);
+        self.grid.row_mut(a.1 + b.1 + 1)[a.0 + b.0 + 1] = self.open;
+        (self.on_step)(self.grid);
+    }
+
+    fn backtracker(&mut self) {
+        let mut visited = vec![false; self.w * self.h];
+        let start = self.random_cell();
+        visited[self.index(start)] = true;
+        self.open_cell(start);
+        let mut stack = vec![start];
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<_> = self
+                .neighbours(cell)
+                .filter(|&n| !visited[self.index(n)])
+                .collect();
+            match unvisited.choose(&mut self.rng) {
+                Some(&next) => {
+                    visited[self.index(next)] = true;
+                    self.join(cell, next);
+                    stack.push(next);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    fn prim(&mut self) {
+        let mut in_maze = vec![false; self.w * self.h];
+        let mut seen = vec![false; self.w * self.h];
+        let mut frontier = vec![];
+        let start = self.random_cell();
+        self.open_cell(start);
+        let mut added = Some(start);
+        while let Some(cell) = added {
+            in_maze[self.index(cell)] = true;
+            seen[self.index(cell)] = true;
+            for n in self.neighbours(cell).collect::<Vec<_>>() {
+                if !seen[self.index(n)] {
+                    seen[self.index(n)] = true;
+                    frontier.push(n);
+                }
+            }
+            added = None;
+            if !frontier.is_empty() {
+                let next = frontier.swap_remove(self.rng.gen_range(0..frontier.len()));
+                let joined: Vec<_> = self
+                    .neighbours(next)
+                    .filter(|&n| in_maze[self.index(n)])
+                    .collect();
+                let &to = joined
+                    .choose(&mut self.rng)
+                    .expect("frontier cells touch the maze");
+                self.join(to, next);
+                added = Some(next);
+            }
+        }
+    }
+
+    fn kruskal(&mut self) {
+        let mut edges = vec![];
+        for y in 0..self.h {
+            for x in 0..self.w {
+                if x + 1 < self.w {
+                    edges.push(((x, y), (x + 1, y)));
+                }
+                if y + 1 < self.h {
+                    edges.push(((x, y), (x, y + 1)));
+                }
+            }
+        }
+        edges.shuffle(&mut self.rng);
+
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        let mut parent: Vec<usize> = (0..self.w * self.h).collect();
+        for (a, b) in edges {
+            let (ra, rb) = (
+                find(&mut parent, self.index(a)),
+                find(&mut parent, self.index(b)),
+            );
+            if ra != rb {
+                parent[ra] = rb;
+                self.join(a, b);
+            }
+        }
+        if self.w * self.h == 1 {
+            self.open_cell((0, 0));
+        }
+    }
+
+    fn wilson(&mut self) {
+        let mut in_maze = vec![false; self.w * self.h];
+        let start = self.random_cell();
+        in_maze[self.index(start)] = true;
+        self.open_cell(start);
+
+        let mut order: Vec<usize> = (0..self.w * self.h).collect();
+        order.shuffle(&mut self.rng);
+        // Where the walk last left each cell. Revisiting a cell overwrites it, which erases
+        // the loop the walk just made.
+        let mut next = vec![0; self.w * self.h];
+        for first in order {
+            if in_maze[first] {
+                continue;
+            }
+            let mut cell = self.cell(first);
+            while !in_maze[self.index(cell)] {
+                let ns: Vec<_> = self.neighbours(cell).collect();
+                let &n = ns
+                    .choose(&mut self.rng)
+                    .expect("the maze has more than one cell");
+                let i = self.index(cell);
+                next[i] = self.index(n);
+                cell = n;
+            }
+            let mut i = first;
+            while !in_maze[i] {
+                in_maze[i] = true;
+                let (a, b) = (self.cell(i), self.cell(next[i]));
+                self.join(a, b);
+                i = next[i];
+            }
+        }
+    }
+
+    fn eller(&mut self) {
+        // The set each cell in the current row belongs to.
+        let mut sets: Vec<Option<usize>> = vec![None; self.w];
+        let mut next_set = 0;
+        for y in 0..self.h {
+            let last = y + 1 == self.h;
+            for (x, set) in sets.iter_mut().enumerate() {
+                if set.is_none() {
+                    *set = Some(next_set);
+                    next_set += 1;
+                }
+                self.grid.row_mut(2 * y + 1)[2 * x + 1] = self.open;
+            }
+            for x in 0..self.w - 1 {
+                if sets[x] != sets[x + 1] && (last || self.rng.gen_bool(0.5)) {
+                    let (kept, merged) = (sets[x], sets[x + 1]);
+                    for set in sets.iter_mut().filter(|s| **s == merged) {
+                        *set = kept;
+                    }
+                    self.join((x, y), (x + 1, y));
+                }
+            }
+            if last {
+                break;
+            }
+
+            // Every set carries on down through at least one of its cells.
+            let mut columns: Vec<usize> = (0..self.w).collect();
+            columns.shuffle(&mut self.rng);
+            let mut below = vec![None; self.w];
+            let mut carried = vec![];
+            for x in columns {
+                let set = sets[x];
+                if !carried.contains(&set) || self.rng.gen_bool(0.5) {
+                    carried.push(set);
+                    below[x] = set;
+                    self.join((x, y), (x, y + 1));
+                }
+            }
+            sets = below;
+        }
+    }
+
+    fn division(&mut self) {
+        let (gw, gh) = (2 * self.w + 1, 2 * self.h + 1);
+        for y in 1..gh - 1 {
+            self.grid.row_mut(y)[1..gw - 1].fill(self.open);
+        }
+        (self.on_step)(self.grid);
+
+        // Regions of cells still to split, as (x, y, w, h).
+        let mut regions = vec![(0, 0, self.w, self.h)];
+        while let Some((x, y, w, h)) = regions.pop() {
+            if w < 2 || h < 2 {
+                continue;
+            }
+            let horizontal = match h.cmp(&w) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal => self.rng.gen(),
+            };
+            if horizontal {
+                // A wall below cell row `at`, with a gap at column `gap`.
+                let (at, gap) = (self.rng.gen_range(0..h - 1), self.rng.gen_range(0..w));
+                let gy = 2 * (y + at) + 2;
+                for gx in 2 * x + 1..2 * (x + w) {
+                    if gx != 2 * (x + gap) + 1 {
+                        self.grid.row_mut(gy)[gx] = self.wall;
+                    }
+                }
+                regions.push((x, y, w, at + 1));
+                regions.push((x, y + at + 1, w, h - at - 1));
+            } else {
+                let (at, gap) = (self.rng.gen_range(0..w - 1), self.rng.gen_range(0..h));
+                let gx = 2 * (x + at) + 2;
+                for gy in 2 * y + 1..2 * (y + h) {
+                    if gy != 2 * (y + gap) + 1 {
+                        self.grid.row_mut(gy)[gx] = self.wall;
+                    }
+                }
+                regions.push((x, y, at + 1, h));
+                regions.push((x + at + 1, y, w - at - 1, h));
+            }
+            (self.on_step)(self.grid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufferHandle, ToText};
+
+    /// Checks `maze` is perfect: every cell reachable, and no loops.
+    fn assert_perfect(maze: &Grid<bool>, algorithm: MazeAlgorithm) {
+        let (w, h) = ((maze.width() - 1) / 2, (maze.height() - 1) / 2);
+        let open = maze.cells().iter().filter(|c| **c).count();
+        // A tree of `w * h` cells has `w * h - 1` passages between them.
+        assert_eq!(open, 2 * w * h - 1, "{algorithm:?} has loops or gaps");
+
+        let mut seen = Grid::new(maze.width(), maze.height(), false);
+        let mut todo = vec![(1, 1)];
+        while let Some((x, y)) = todo.pop() {
+            if !maze.get(x, y).unwrap() || seen.get(x, y).unwrap() {
+                continue;
+            }
+            seen.set(x, y, true).unwrap();
+            todo.extend([(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]);
+        }
+        assert_eq!(&seen, maze, "{algorithm:?} isn't connected");
+    }
+
+    #[test]
+    fn every_algorithm_makes_perfect_mazes() {
+        for algorithm in MazeAlgorithm::ALL {
+            for (w, h) in [(21, 15), (3, 9), (11, 3), (4, 6)] {
+                let mut maze = Grid::new(w, h, false);
+                MazeGenerator::new(algorithm, 7).generate(&mut maze, false, true);
+                assert_perfect(&maze, algorithm);
+            }
+            assert_eq!(MazeAlgorithm::from_name(algorithm.name()), Some(algorithm));
+        }
+    }
+
+    #[test]
+    fn seeds_are_reproducible() {
+        let make = |seed| {
+            let mut maze = Grid::new(15, 15, '#');
+            MazeGenerator::new(MazeAlgorithm::Wilson, seed).generate(&mut maze, '#', '.');
+            maze.to_text(|c| c)
+        };
+        assert_eq!(make(1), make(1));
+        assert_ne!(make(1), make(2));
+    }
+
+    #[test]
+    fn animation_ends_on_the_finished_maze() {
+        let gen = MazeGenerator::new(MazeAlgorithm::Eller, 3);
+        let mut buf = BufferHandle::with_value(9, 7, 0u8);
+        let mut frames = 0;
+        gen.generate_with(&mut Grid::new(9, 7, 0u8), 0, 1, |_| frames += 1);
+        // A 4x3 cell maze has 11 passages.
+        assert_eq!(frames, 11);
+
+        gen.animate(&mut buf, 0, 1);
+        let mut maze = Grid::new(9, 7, 0u8);
+        gen.generate(&mut maze, 0, 1);
+        buf.render(|f| assert_eq!(f, &maze));
+    }
+}
@@ -0,0 +1,154 @@
+/// Which cells around a centre count as its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighbourhood<'a> {
+    /// The 4 orthogonal cells.
+    VonNeumann,
+    /// The 8 surrounding cells.
+    Moore,
+    /// Every cell within a (2n+1) square, minus the centre.
+    Radius(usize),
+    /// User supplied (dx, dy) offsets, visited in the given order.
+    Offsets(&'a [(isize, isize)]),
+}
+
+const VON_NEUMANN: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+const MOORE: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+impl<'a> Neighbourhood<'a> {
+    pub fn offsets(&self) -> Offsets<'a> {
+        Offsets { shape: *self, i: 0 }
+    }
+}
+
+/// Iterator over the (dx, dy) offsets of a `Neighbourhood`.
+pub struct Offsets<'a> {
+    shape: Neighbourhood<'a>,
+    i: usize,
+}
+
+impl Iterator for Offsets<'_> {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = match self.shape {
+            Neighbourhood::VonNeumann => VON_NEUMANN.get(self.i).copied(),
+            Neighbourhood::Moore => MOORE.get(self.i).copied(),
+            Neighbourhood::Offsets(offsets) => offsets.get(self.i).copied(),
+            Neighbourhood::Radius(r) => {
+                let side = 2 * r + 1;
+                // Skip over the centre cell, which sits in the middle of the square.
+                let i = if self.i >= side * side / 2 {
+                    self.i + 1
+                } else {
+                    self.i
+                };
+                (i < side * side).then(|| {
+                    (
+                        (i % side) as isize - r as isize,
+                        (i / side) as isize - r as isize,
+                    )
+                })
+            }
+        };
+        self.i += 1;
+        offset
+    }
+}
+
+/// What to do when a neighbour falls outside the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edge<C> {
+    /// Leave it out.
+    Skip,
+    /// Use the nearest cell on the border.
+    Clamp,
+    /// Wrap around to the opposite side (toroidal).
+    Wrap,
+    /// Pretend the outside is filled with this value.
+    Constant(C),
+}
+
+impl<C> Edge<C> {
+    /// Maps a possibly out of range coordinate onto the grid, or `None` if there's no cell for it.
+    pub fn resolve(
+        &self,
+        x: isize,
+        y: isize,
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (w, h) = (width as isize, height as isize);
+        match self {
+            Edge::Clamp => Some((x.clamp(0, w - 1) as usize, y.clamp(0, h - 1) as usize)),
+            Edge::Wrap => Some((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize)),
+            Edge::Skip | Edge::Constant(_) => {
+                ((0..w).contains(&x) && (0..h).contains(&y)).then_some((x as usize, y as usize))
+            }
+        }
+    }
+}
+
+/// A single neighbour of a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Neighbour<C> {
+    /// Offset from the centre cell.
+    pub offset: (isize, isize),
+    /// Where the value came from, `None` when it was an `Edge::Constant` fill.
+    pub pos: Option<(usize, usize)>,
+    pub value: C,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Grid, TwoDeeRead};
+
+    fn numbered() -> Grid<usize> {
+        Grid::from_vec(3, 3, (0..9).collect()).unwrap()
+    }
+
+    #[test]
+    fn radius_skips_centre() {
+        let offsets: Vec<_> = Neighbourhood::Radius(1).offsets().collect();
+        assert_eq!(offsets, MOORE.to_vec());
+        assert_eq!(Neighbourhood::Radius(2).offsets().count(), 24);
+    }
+
+    #[test]
+    fn edge_policies_at_corner() {
+        let grid = numbered();
+        let values = |edge| {
+            grid.neighbours(0, 0, Neighbourhood::VonNeumann, edge)
+                .map(|n| n.value)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(Edge::Skip), vec![1, 3]);
+        assert_eq!(values(Edge::Clamp), vec![0, 1, 3, 0]);
+        assert_eq!(values(Edge::Wrap), vec![6, 1, 3, 2]);
+        assert_eq!(values(Edge::Constant(42)), vec![42, 1, 3, 42]);
+    }
+
+    #[test]
+    fn custom_offsets() {
+        let grid = numbered();
+        let knight = [(1, 2), (2, 1)];
+        let found: Vec<_> = grid
+            .neighbours(0, 0, Neighbourhood::Offsets(&knight), Edge::Skip)
+            .map(|n| n.pos.unwrap())
+            .collect();
+        assert_eq!(found, vec![(1, 2), (2, 1)]);
+    }
+}
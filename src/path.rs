@@ -0,0 +1,622 @@
+//! Shortest paths over grids, with the cost of each cell coming from a closure.
+//!
+//! Searches fill in a `Search`: the distance to every cell they reached, how they got there,
+//! and the path found if they were given a goal. The `_with` and `animate` versions also
+//! report which cells are on the frontier and which are done after every cell expanded, for
+//! showing a search as it runs.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::f32::consts::SQRT_2;
+use std::marker::PhantomData;
+
+use crate::buf::{Grid, Publisher, TwoDeeBuffer, TwoDeeRead};
+use crate::neighbours::Neighbourhood;
+
+/// Which steps a path may take.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Moves {
+    /// Up, down, left and right.
+    #[default]
+    Four,
+    /// Diagonals too, costing `SQRT_2` times as much. A diagonal step can't squeeze between
+    /// two blocked cells, or cut the corner of one.
+    Eight,
+}
+
+impl Moves {
+    fn neighbourhood(self) -> Neighbourhood<'static> {
+        match self {
+            Moves::Four => Neighbourhood::VonNeumann,
+            Moves::Eight => Neighbourhood::Moore,
+        }
+    }
+}
+
+/// A* estimates of the cost left to the goal. They assume no cell costs less than 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Heuristic {
+    /// `dx + dy`, for `Moves::Four`.
+    Manhattan,
+    /// Diagonal steps as far as they go then straight ones, for `Moves::Eight`.
+    Octile,
+}
+
+impl Heuristic {
+    pub fn estimate(self, from: (usize, usize), to: (usize, usize)) -> f32 {
+        let dx = from.0.abs_diff(to.0) as f32;
+        let dy = from.1.abs_diff(to.1) as f32;
+        match self {
+            Heuristic::Manhattan => dx + dy,
+            Heuristic::Octile => dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathAlgorithm {
+    /// Breadth first, counting steps and ignoring cell costs.
+    Bfs,
+    Dijkstra,
+    /// Dijkstra steered towards the goal. Without a goal it's plain Dijkstra.
+    AStar(Heuristic),
+    /// BFS from both ends at once, stopping where they meet. Without a goal it's plain BFS.
+    BidirectionalBfs,
+}
+
+/// Where a cell is in a search, for drawing it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mark {
+    #[default]
+    Unseen,
+    /// Reached, waiting to be expanded.
+    Frontier,
+    /// Expanded.
+    Visited,
+    /// On the path found.
+    Path,
+}
+
+/// Lets a `Palette` colour marks directly.
+impl From<Mark> for usize {
+    fn from(m: Mark) -> usize {
+        m as usize
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Path {
+    /// From the start to the goal, both included.
+    pub cells: Vec<(usize, usize)>,
+    pub cost: f32,
+}
+
+/// What a search found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    /// Cost from the start to each cell reached, `f32::INFINITY` for the rest. Bidirectional
+    /// searches only fill in cells reached from the start end.
+    pub distances: Grid<f32>,
+    pub marks: Grid<Mark>,
+    /// The path to the goal, if there was one and it could be reached.
+    pub path: Option<Path>,
+    came_from: Grid<Option<(usize, usize)>>,
+}
+
+impl Search {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            distances: Grid::new(width, height, f32::INFINITY),
+            marks: Grid::new(width, height, Mark::Unseen),
+            path: None,
+            came_from: Grid::new(width, height, None),
+        }
+    }
+
+    pub fn distance(&self, x: usize, y: usize) -> Option<f32> {
+        self.distances.get(x, y).ok().filter(|d| d.is_finite())
+    }
+
+    /// The path found from the start to `to`, which the search must have reached.
+    pub fn path_to(&self, to: (usize, usize)) -> Option<Path> {
+        let cost = self.distance(to.0, to.1)?;
+        let (mut cells, mut at) = (vec![to], to);
+        while let Some(prev) = self.came_from.row(at.1)[at.0] {
+            cells.push(prev);
+            at = prev;
+        }
+        cells.reverse();
+        Some(Path { cells, cost })
+    }
+
+    fn mark_path(&mut self) {
+        for &(x, y) in self.path.iter().flat_map(|p| &p.cells) {
+            self.marks.row_mut(y)[x] = Mark::Path;
+        }
+    }
+}
+
+/// Finds paths over grids of `C`, where `cost` gives what stepping onto a cell costs, or
+/// `None` if it can't be entered. The start cell is never checked.
+#[derive(Clone, Copy, Debug)]
+pub struct Pathfinder<F> {
+    pub moves: Moves,
+    cost: F,
+}
+
+impl<F> Pathfinder<F> {
+    pub fn new(cost: F) -> Self {
+        Self {
+            moves: Moves::Four,
+            cost,
+        }
+    }
+
+    pub fn with_moves(mut self, moves: Moves) -> Self {
+        self.moves = moves;
+        self
+    }
+
+    /// Searches `grid` from `start`. With no `goal`, BFS and Dijkstra map out everything
+    /// reachable.
+    pub fn search<C, B>(
+        &self,
+        grid: &B,
+        start: (usize, usize),
+        goal: Option<(usize, usize)>,
+        algorithm: PathAlgorithm,
+    ) -> Search
+    where
+        C: Copy,
+        B: TwoDeeRead<C> + ?Sized,
+        F: Fn(&C) -> Option<f32>,
+    {
+        self.search_with(grid, start, goal, algorithm, |_| {})
+    }
+
+    /// As `search`, calling `on_step` with the marks so far after each cell is expanded.
+    pub fn search_with<C, B, S>(
+        &self,
+        grid: &B,
+        start: (usize, usize),
+        goal: Option<(usize, usize)>,
+        algorithm: PathAlgorithm,
+        on_step: S,
+    ) -> Search
+    where
+        C: Copy,
+        B: TwoDeeRead<C> + ?Sized,
+        F: Fn(&C) -> Option<f32>,
+        S: FnMut(&Grid<Mark>),
+    {
+        let mut searcher = Searcher {
+            grid,
+            finder: self,
+            search: Search::new(grid.width(), grid.height()),
+            on_step,
+            cell: PhantomData,
+        };
+        if grid.check_coords(start.0, start.1).is_ok() {
+            match (algorithm, goal) {
+                (PathAlgorithm::Bfs, _) | (PathAlgorithm::BidirectionalBfs, None) => {
+                    searcher.bfs(start, goal)
+                }
+                (PathAlgorithm::Dijkstra, _) => searcher.best_first(start, goal, None),
+                (PathAlgorithm::AStar(h), _) => searcher.best_first(start, goal, Some(h)),
+                (PathAlgorithm::BidirectionalBfs, Some(goal)) => {
+                    searcher.bidirectional(start, goal)
+                }
+            }
+        }
+        let mut search = searcher.search;
+        search.mark_path();
+        search
+    }
+
+    /// Runs a search over `grid`, publishing each step through `buf` with every cell painted
+    /// by `paint`. The last frame shows the path.
+    pub fn animate<C, B, P>(
+        &self,
+        grid: &B,
+        start: (usize, usize),
+        goal: Option<(usize, usize)>,
+        algorithm: PathAlgorithm,
+        buf: &mut P,
+        paint: impl Fn(C, Mark) -> u32,
+    ) -> Search
+    where
+        C: Copy,
+        B: TwoDeeRead<C> + ?Sized,
+        F: Fn(&C) -> Option<f32>,
+        P: Publisher<u32>,
+    {
+        let mut publish = |marks: &Grid<Mark>| {
+            buf.publish(|f| {
+                for (y, out) in f.rows_mut().enumerate() {
+                    for (x, px) in out.iter_mut().enumerate() {
+                        if let (Ok(cell), Ok(mark)) = (grid.get(x, y), marks.get(x, y)) {
+                            *px = paint(cell, mark);
+                        }
+                    }
+                }
+            })
+        };
+        let search = self.search_with(grid, start, goal, algorithm, &mut publish);
+        publish(&search.marks);
+        search
+    }
+}
+
+/// An entry in the best-first queue, ordered so the cheapest estimate comes out first.
+struct Queued {
+    estimate: f32,
+    pos: (usize, usize),
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+struct Searcher<'a, C, B: ?Sized, F, S> {
+    grid: &'a B,
+    finder: &'a Pathfinder<F>,
+    search: Search,
+    on_step: S,
+    cell: PhantomData<C>,
+}
+
+impl<C, B, F, S> Searcher<'_, C, B, F, S>
+where
+    C: Copy,
+    B: TwoDeeRead<C> + ?Sized,
+    F: Fn(&C) -> Option<f32>,
+    S: FnMut(&Grid<Mark>),
+{
+    fn cost(&self, (x, y): (usize, usize)) -> Option<f32> {
+        (self.finder.cost)(&self.grid.row(y)[x])
+    }
+
+    /// The cells one step from `from`, and what stepping onto each costs.
+    fn steps(&self, from: (usize, usize)) -> Vec<((usize, usize), f32)> {
+        let (w, h) = (self.grid.width(), self.grid.height());
+        let inside = |dx: isize, dy: isize| {
+            let (x, y) = (
+                from.0.checked_add_signed(dx)?,
+                from.1.checked_add_signed(dy)?,
+            );
+            (x < w && y < h).then_some((x, y))
+        };
+        self.finder
+            .moves
+            .neighbourhood()
+            .offsets()
+            .filter_map(|(dx, dy)| {
+                let to = inside(dx, dy)?;
+                let cost = self.cost(to)?;
+                if dx != 0 && dy != 0 {
+                    // Both cells beside the diagonal have to be open too.
+                    self.cost(inside(dx, 0)?)?;
+                    self.cost(inside(0, dy)?)?;
+                    return Some((to, cost * SQRT_2));
+                }
+                Some((to, cost))
+            })
+            .collect()
+    }
+
+    fn reach(&mut self, to: (usize, usize), from: Option<(usize, usize)>, distance: f32) {
+        self.search.distances.row_mut(to.1)[to.0] = distance;
+        self.search.came_from.row_mut(to.1)[to.0] = from;
+        self.search.marks.row_mut(to.1)[to.0] = Mark::Frontier;
+    }
+
+    fn expand(&mut self, (x, y): (usize, usize)) {
+        self.search.marks.row_mut(y)[x] = Mark::Visited;
+        (self.on_step)(&self.search.marks);
+    }
+
+    fn bfs(&mut self, start: (usize, usize), goal: Option<(usize, usize)>) {
+        self.reach(start, None, 0.0);
+        let mut queue = VecDeque::from([start]);
+        while let Some(pos) = queue.pop_front() {
+            self.expand(pos);
+            if Some(pos) == goal {
+                self.search.path = self.search.path_to(pos);
+                return;
+            }
+            let distance = self.search.distances.row(pos.1)[pos.0] + 1.0;
+            for (to, _) in self.steps(pos) {
+                if self.search.marks.row(to.1)[to.0] == Mark::Unseen {
+                    self.reach(to, Some(pos), distance);
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+
+    fn best_first(
+        &mut self,
+        start: (usize, usize),
+        goal: Option<(usize, usize)>,
+        heuristic: Option<Heuristic>,
+    ) {
+        let estimate = |pos| match (heuristic, goal) {
+            (Some(h), Some(goal)) => h.estimate(pos, goal),
+            _ => 0.0,
+        };
+        self.reach(start, None, 0.0);
+        let mut queue = BinaryHeap::from([Queued {
+            estimate: estimate(start),
+            pos: start,
+        }]);
+        while let Some(Queued { pos, .. }) = queue.pop() {
+            if self.search.marks.row(pos.1)[pos.0] == Mark::Visited {
+                // Already expanded through a cheaper route.
+                continue;
+            }
+            self.expand(pos);
+            if Some(pos) == goal {
+                self.search.path = self.search.path_to(pos);
+                return;
+            }
+            let here = self.search.distances.row(pos.1)[pos.0];
+            for (to, cost) in self.steps(pos) {
+                let distance = here + cost;
+                if distance < self.search.distances.row(to.1)[to.0] {
+                    self.reach(to, Some(pos), distance);
+                    queue.push(Queued {
+                        estimate: distance + estimate(to),
+                        pos: to,
+                    });
+                }
+            }
+        }
+    }
+
+    fn bidirectional(&mut self, start: (usize, usize), goal: (usize, usize)) {
+        self.reach(start, None, 0.0);
+        if self.grid.check_coords(goal.0, goal.1).is_err() || self.cost(goal).is_none() {
+            return;
+        }
+        // The goal end searches backwards, sharing our marks but keeping its own distances.
+        let mut back = Search::new(self.grid.width(), self.grid.height());
+        back.distances.row_mut(goal.1)[goal.0] = 0.0;
+        self.search.marks.row_mut(goal.1)[goal.0] = Mark::Frontier;
+        let mut fronts = [vec![start], vec![goal]];
+        // The cost of the best path so far, and the cells either side of where the ends met.
+        let mut best = (start == goal).then_some((0.0, start, goal));
+
+        while best.is_none() && fronts.iter().all(|f| !f.is_empty()) {
+            // Grow the smaller side by a whole layer, keeping the cheapest place it meets the
+            // other side.
+            let side = usize::from(fronts[1].len() < fronts[0].len());
+            let mut next = vec![];
+            for pos in std::mem::take(&mut fronts[side]) {
+                self.expand(pos);
+                let (steps, ours, theirs) = if side == 0 {
+                    (self.steps(pos), &mut self.search, &back)
+                } else {
+                    (self.steps_back(pos, start), &mut back, &self.search)
+                };
+                let distance = ours.distances.row(pos.1)[pos.0] + 1.0;
+                let mut reached = vec![];
+                for (to, _) in steps {
+                    let total = distance + theirs.distances.row(to.1)[to.0];
+                    if total.is_finite() && best.is_none_or(|(b, ..)| total < b) {
+                        let (s, g) = if side == 0 { (pos, to) } else { (to, pos) };
+                        best = Some((total, s, g));
+                    }
+                    if ours.distances.row(to.1)[to.0].is_infinite() {
+                        ours.distances.row_mut(to.1)[to.0] = distance;
+                        ours.came_from.row_mut(to.1)[to.0] = Some(pos);
+                        reached.push(to);
+                    }
+                }
+                for &(x, y) in &reached {
+                    if self.search.marks.row(y)[x] == Mark::Unseen {
+                        self.search.marks.row_mut(y)[x] = Mark::Frontier;
+                    }
+                }
+                next.extend(reached);
+            }
+            fronts[side] = next;
+        }
+
+        if let Some((cost, s, g)) = best {
+            let mut cells = self
+                .search
+                .path_to(s)
+                .expect("reached from the start")
+                .cells;
+            if g != s {
+                cells.extend(
+                    back.path_to(g)
+                        .expect("reached from the goal")
+                        .cells
+                        .into_iter()
+                        .rev(),
+                );
+            }
+            self.search.path = Some(Path { cells, cost });
+        }
+    }
+
+    /// Cells `to` that could step onto `from`, for searching backwards from the goal.
+    fn steps_back(
+        &self,
+        from: (usize, usize),
+        start: (usize, usize),
+    ) -> Vec<((usize, usize), f32)> {
+        let (w, h) = (self.grid.width(), self.grid.height());
+        let inside = |dx: isize, dy: isize| {
+            let (x, y) = (
+                from.0.checked_add_signed(dx)?,
+                from.1.checked_add_signed(dy)?,
+            );
+            (x < w && y < h).then_some((x, y))
+        };
+        let open = |pos| pos == start || self.cost(pos).is_some();
+        self.finder
+            .moves
+            .neighbourhood()
+            .offsets()
+            .filter_map(|(dx, dy)| {
+                let to = inside(dx, dy)?;
+                if !open(to) {
+                    return None;
+                }
+                if dx != 0 && dy != 0 && !(open(inside(dx, 0)?) && open(inside(0, dy)?)) {
+                    return None;
+                }
+                Some((to, 1.0))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufferHandle, ToText};
+
+    const CAVE: &str = "\
+S..#......
+.#.#.####.
+.#...#....
+.#####.##.
+......#..G";
+
+    fn cave() -> (Grid<char>, (usize, usize), (usize, usize)) {
+        let grid = Grid::parse(CAVE, |c| c).unwrap();
+        let find = |want| grid.iter_coords().find(|(_, c)| **c == want).unwrap().0;
+        let (start, goal) = (find('S'), find('G'));
+        (grid, start, goal)
+    }
+
+    fn walls(c: &char) -> Option<f32> {
+        (*c != '#').then_some(1.0)
+    }
+
+    #[test]
+    fn every_algorithm_finds_the_shortest_path() {
+        let (grid, start, goal) = cave();
+        let finder = Pathfinder::new(walls);
+        for algorithm in [
+            PathAlgorithm::Bfs,
+            PathAlgorithm::Dijkstra,
+            PathAlgorithm::AStar(Heuristic::Manhattan),
+            PathAlgorithm::BidirectionalBfs,
+        ] {
+            let search = finder.search(&grid, start, Some(goal), algorithm);
+            let path = search.path.expect("the goal is reachable");
+            assert_eq!(path.cost, 17.0, "{algorithm:?}");
+            assert_eq!(path.cells.len(), 18, "{algorithm:?}");
+            assert_eq!((path.cells[0], path.cells[17]), (start, goal));
+            for pair in path.cells.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1, "{algorithm:?}");
+                assert_ne!(grid.get(b.0, b.1), Ok('#'));
+            }
+        }
+    }
+
+    #[test]
+    fn distance_maps_and_costs() {
+        let (grid, start, _) = cave();
+        let map = Pathfinder::new(walls).search(&grid, start, None, PathAlgorithm::Bfs);
+        assert_eq!(map.distance(2, 2), Some(4.0));
+        assert_eq!(map.distance(3, 0), None);
+        assert_eq!(map.distance(0, 4), Some(4.0));
+        assert_eq!(map.path_to((0, 4)).unwrap().cells.len(), 5);
+
+        // Mud costs 5 to cross, so the cheapest route goes around it.
+        let grid = Grid::parse("S~~G\n....", |c| c).unwrap();
+        let mud = |c: &char| Some(if *c == '~' { 5.0 } else { 1.0 });
+        let finder = Pathfinder::new(mud);
+        let path = finder
+            .search(&grid, (0, 0), Some((3, 0)), PathAlgorithm::Dijkstra)
+            .path
+            .unwrap();
+        assert_eq!(path.cost, 5.0);
+        let bfs = finder.search(&grid, (0, 0), Some((3, 0)), PathAlgorithm::Bfs);
+        assert_eq!(bfs.path.unwrap().cost, 3.0);
+    }
+
+    #[test]
+    fn diagonal_moves_and_octile() {
+        let grid = Grid::parse(".....\n.....\n.....\n..#..\n.....", |c| c).unwrap();
+        let finder = Pathfinder::new(walls).with_moves(Moves::Eight);
+        let astar = finder.search(
+            &grid,
+            (0, 0),
+            Some((4, 4)),
+            PathAlgorithm::AStar(Heuristic::Octile),
+        );
+        let dijkstra = finder.search(&grid, (0, 0), Some((4, 4)), PathAlgorithm::Dijkstra);
+        let (a, d) = (
+            astar.path.as_ref().unwrap(),
+            dijkstra.path.as_ref().unwrap(),
+        );
+        assert!((a.cost - d.cost).abs() < 1e-4);
+        // Can't go straight down the diagonal past the rock, so one straight step is needed.
+        assert!((a.cost - (3.0 * SQRT_2 + 2.0)).abs() < 1e-4, "{}", a.cost);
+        // A* should look at fewer cells to get there.
+        let visited = |s: &Search| {
+            s.marks
+                .cells()
+                .iter()
+                .filter(|m| **m != Mark::Unseen)
+                .count()
+        };
+        assert!(visited(&astar) < visited(&dijkstra));
+        assert!((Heuristic::Octile.estimate((0, 0), (3, 1)) - (2.0 + SQRT_2)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn animation_paints_the_path() {
+        let (grid, start, goal) = cave();
+        let mut buf = BufferHandle::with_value(grid.width(), grid.height(), 0u32);
+        let mut steps = 0;
+        Pathfinder::new(walls)
+            .search_with(&grid, start, Some(goal), PathAlgorithm::Bfs, |_| steps += 1);
+        assert!(steps >= 18);
+
+        let search = Pathfinder::new(walls).animate(
+            &grid,
+            start,
+            Some(goal),
+            PathAlgorithm::Bfs,
+            &mut buf,
+            |c, mark| if c == '#' { 9 } else { mark as u32 },
+        );
+        buf.render(|f| {
+            let text = f.to_text(|c| char::from_digit(c, 10).unwrap());
+            assert_eq!(text.lines().next(), Some("3339333333"));
+            assert_eq!(
+                text.matches('3').count(),
+                search.path.as_ref().unwrap().cells.len()
+            );
+        });
+    }
+}
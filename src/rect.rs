@@ -0,0 +1,71 @@
+/// Axis-aligned rectangle of cells, anchored at its top left corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Rect covering a whole `width` x `height` grid.
+    pub const fn of_size(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    /// One past the rightmost column, stopping at `usize::MAX` for rects that reach past it.
+    pub const fn right(&self) -> usize {
+        self.x.saturating_add(self.width)
+    }
+
+    /// One past the bottom row, stopping at `usize::MAX` for rects that reach past it.
+    pub const fn bottom(&self) -> usize {
+        self.y.saturating_add(self.height)
+    }
+
+    pub const fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub const fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Overlap of the two rects, empty (but still positioned) if they don't touch.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right()).max(x);
+        let bottom = self.bottom().min(other.bottom()).max(y);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Smallest rect containing both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
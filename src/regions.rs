@@ -0,0 +1,321 @@
+//! Flood fills and connected-component labelling.
+//!
+//! Both walk regions a row at a time with a scanline fill, so they only keep a seed per run
+//! of cells rather than one per cell.
+
+use std::ops::Range;
+
+use crate::buf::{Grid, TwoDeeBuffer, TwoDeeRead};
+use crate::colour::Palette;
+use crate::pack_rgba;
+use crate::rect::Rect;
+
+/// Which cells touching a cell count as connected to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Connectivity {
+    /// Edges only.
+    #[default]
+    Four,
+    /// Corners too.
+    Eight,
+}
+
+/// Visits the region around `start` of unvisited cells that `inside` accepts, calling `run`
+/// with each row's runs of cells and marking them in `visited`.
+fn scan_region(
+    (w, h): (usize, usize),
+    start: (usize, usize),
+    connectivity: Connectivity,
+    visited: &mut [bool],
+    inside: impl Fn(usize, usize) -> bool,
+    mut run: impl FnMut(usize, Range<usize>),
+) {
+    let mut seeds = vec![start];
+    while let Some((x, y)) = seeds.pop() {
+        if visited[y * w + x] || !inside(x, y) {
+            continue;
+        }
+        let (mut lo, mut hi) = (x, x + 1);
+        while lo > 0 && !visited[y * w + lo - 1] && inside(lo - 1, y) {
+            lo -= 1;
+        }
+        while hi < w && !visited[y * w + hi] && inside(hi, y) {
+            hi += 1;
+        }
+        visited[y * w + lo..y * w + hi].fill(true);
+        run(y, lo..hi);
+
+        // Seed the start of every run touching this one in the rows either side.
+        let (from, to) = match connectivity {
+            Connectivity::Four => (lo, hi),
+            Connectivity::Eight => (lo.saturating_sub(1), (hi + 1).min(w)),
+        };
+        for ny in [y.checked_sub(1), (y + 1 < h).then_some(y + 1)]
+            .into_iter()
+            .flatten()
+        {
+            let mut in_run = false;
+            for nx in from..to {
+                let open = !visited[ny * w + nx] && inside(nx, ny);
+                if open && !in_run {
+                    seeds.push((nx, ny));
+                }
+                in_run = open;
+            }
+        }
+    }
+}
+
+/// Marks every cell connected to `start` through cells `inside` accepts. `start` itself is
+/// only included if `inside` accepts it.
+pub fn flood_mask<C, B>(
+    grid: &B,
+    start: (usize, usize),
+    connectivity: Connectivity,
+    inside: impl Fn(&C) -> bool,
+) -> Grid<bool>
+where
+    C: Copy,
+    B: TwoDeeRead<C> + ?Sized,
+{
+    let (w, h) = (grid.width(), grid.height());
+    let mut mask = Grid::new(w, h, false);
+    if grid.check_coords(start.0, start.1).is_ok() {
+        let mut visited = vec![false; w * h];
+        scan_region(
+            (w, h),
+            start,
+            connectivity,
+            &mut visited,
+            |x, y| inside(&grid.row(y)[x]),
+            |y, xs| mask.row_mut(y)[xs].fill(true),
+        );
+    }
+    mask
+}
+
+/// Paint-bucket fill: sets the region of cells equal to the one at `start`, and connected to
+/// it, to `value`. Returns how many cells were filled.
+pub fn flood_fill<C, B>(
+    grid: &mut B,
+    start: (usize, usize),
+    value: C,
+    connectivity: Connectivity,
+) -> usize
+where
+    C: Copy + PartialEq,
+    B: TwoDeeBuffer<C> + ?Sized,
+{
+    let Ok(target) = grid.get(start.0, start.1) else {
+        return 0;
+    };
+    let (w, h) = (grid.width(), grid.height());
+    let mut runs = vec![];
+    scan_region(
+        (w, h),
+        start,
+        connectivity,
+        &mut vec![false; w * h],
+        |x, y| grid.row(y)[x] == target,
+        |y, xs| runs.push((y, xs)),
+    );
+    let mut filled = 0;
+    for (y, xs) in runs {
+        filled += xs.len();
+        grid.row_mut(y)[xs].fill(value);
+    }
+    filled
+}
+
+/// One connected region found by `label_components`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Component {
+    pub label: usize,
+    /// Number of cells.
+    pub area: usize,
+    pub bounds: Rect,
+    /// Number of cell edges between the region and anything else, the grid border included.
+    /// Edges are counted the same way for either connectivity.
+    pub perimeter: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Components {
+    /// Each cell's component label, counting up from 1 in the order the regions are first
+    /// met reading the grid. Cells left out are 0.
+    pub labels: Grid<usize>,
+    /// Indexed by `label - 1`.
+    pub components: Vec<Component>,
+}
+
+impl Components {
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// The component with `label`, if there is one.
+    pub fn get(&self, label: usize) -> Option<&Component> {
+        self.components.get(label.checked_sub(1)?)
+    }
+
+    /// The component covering (x, y).
+    pub fn at(&self, x: usize, y: usize) -> Option<&Component> {
+        self.get(self.labels.get(x, y).ok()?)
+    }
+
+    /// Black for unlabelled cells, then a random colour for each component, to show
+    /// `labels` with.
+    pub fn palette(&self, seed: u64) -> Palette {
+        let mut palette = Palette::random(self.len() + 1, seed);
+        palette.colours[0] = pack_rgba(0, 0, 0, 255);
+        palette
+    }
+}
+
+/// Splits `grid` into regions of connected, equal cells. Cells `include` rejects are left
+/// out as background, e.g. `|c| *c` for a `bool` image or `|_| true` to label everything.
+pub fn label_components<C, B>(
+    grid: &B,
+    connectivity: Connectivity,
+    include: impl Fn(&C) -> bool,
+) -> Components
+where
+    C: Copy + PartialEq,
+    B: TwoDeeRead<C> + ?Sized,
+{
+    let (w, h) = (grid.width(), grid.height());
+    let mut labels = Grid::new(w, h, 0);
+    let mut components: Vec<Component> = vec![];
+    // Regions never overlap, so one visited mask serves for all of them.
+    let mut visited = vec![false; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let value = grid.row(y)[x];
+            if visited[y * w + x] || !include(&value) {
+                continue;
+            }
+            let mut component = Component {
+                label: components.len() + 1,
+                area: 0,
+                bounds: Rect::default(),
+                perimeter: 0,
+            };
+            scan_region(
+                (w, h),
+                (x, y),
+                connectivity,
+                &mut visited,
+                |x, y| grid.row(y)[x] == value,
+                |y, xs| {
+                    component.area += xs.len();
+                    component.bounds = component.bounds.union(&Rect::new(xs.start, y, xs.len(), 1));
+                    labels.row_mut(y)[xs].fill(component.label);
+                },
+            );
+            components.push(component);
+        }
+    }
+
+    for ((x, y), &label) in labels.iter_coords() {
+        if label == 0 {
+            continue;
+        }
+        let outside = [
+            x == 0 || labels.row(y)[x - 1] != label,
+            x + 1 == w || labels.row(y)[x + 1] != label,
+            y == 0 || labels.row(y - 1)[x] != label,
+            y + 1 == h || labels.row(y + 1)[x] != label,
+        ];
+        components[label - 1].perimeter += outside.iter().filter(|o| **o).count();
+    }
+    Components { labels, components }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToText;
+
+    #[test]
+    fn fills_four_and_eight_connected() {
+        let start = Grid::parse("..#..\n.#.#.\n#...#\n.#.#.\n..#..", |c| c).unwrap();
+        let mut grid = start.clone();
+        // The diamond's inside doesn't reach its corners through edges alone.
+        assert_eq!(flood_fill(&mut grid, (2, 2), 'o', Connectivity::Four), 5);
+        assert_eq!(grid.to_text(|c| c), "..#..\n.#o#.\n#ooo#\n.#o#.\n..#..\n");
+        assert_eq!(flood_fill(&mut grid, (2, 2), 'o', Connectivity::Four), 5);
+
+        let mut grid = start.clone();
+        assert_eq!(flood_fill(&mut grid, (0, 0), '~', Connectivity::Eight), 17);
+        assert_eq!(flood_fill(&mut grid, (9, 9), '~', Connectivity::Eight), 0);
+
+        let mask = flood_mask(&start, (0, 0), Connectivity::Four, |c| *c == '.');
+        assert_eq!(mask.cells().iter().filter(|m| **m).count(), 3);
+        assert!(
+            !flood_mask(&start, (2, 0), Connectivity::Four, |c| *c == '.')
+                .get(2, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn garden_regions() {
+        // Advent of Code 2024 day 12's first example.
+        let garden = Grid::parse("AAAA\nBBCD\nBBCC\nEEEC", |c| c).unwrap();
+        let regions = label_components(&garden, Connectivity::Four, |_| true);
+        let summary: Vec<_> = regions
+            .components
+            .iter()
+            .map(|c| {
+                (
+                    garden.get(c.bounds.x, c.bounds.y).unwrap(),
+                    c.area,
+                    c.perimeter,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ('A', 4, 10),
+                ('B', 4, 8),
+                ('C', 4, 10),
+                ('D', 1, 4),
+                ('E', 3, 8)
+            ]
+        );
+        let price: usize = regions
+            .components
+            .iter()
+            .map(|c| c.area * c.perimeter)
+            .sum();
+        assert_eq!(price, 140);
+        assert_eq!(regions.at(3, 3).unwrap().bounds, Rect::new(2, 1, 2, 3));
+    }
+
+    #[test]
+    fn labels_a_binary_image() {
+        let image = Grid::parse("##..#\n#...#\n.#...\n....#", |c| c == '#').unwrap();
+        let four = label_components(&image, Connectivity::Four, |c| *c);
+        assert_eq!(
+            four.labels
+                .to_text(|l| char::from_digit(l as u32, 10).unwrap()),
+            "11002\n10002\n03000\n00004\n"
+        );
+        assert_eq!(four.get(2).unwrap().bounds, Rect::new(4, 0, 1, 2));
+        assert_eq!(four.get(0), None);
+
+        let eight = label_components(&image, Connectivity::Eight, |c| *c);
+        assert_eq!(eight.len(), 3);
+        let palette = eight.palette(1);
+        assert_eq!(palette.colours.len(), 4);
+        assert_eq!(palette.colour(0usize), pack_rgba(0, 0, 0, 255));
+    }
+}
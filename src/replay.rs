@@ -0,0 +1,446 @@
+//! Recording every published frame of a run as a log of cell writes, and playing it back.
+//!
+//! Layout, all integers little endian:
+//!
+//! | bytes | field                                   |
+//! |-------|-----------------------------------------|
+//! | 4     | magic, `SLOG`                           |
+//! | 1     | format version                          |
+//! | 1     | cell type tag, see `SnapshotCell::TAG`  |
+//! | 2     | reserved, zero                          |
+//! | 8     | RNG seed the run was started with       |
+//! | 4     | width                                   |
+//! | 4     | height                                  |
+//! | ...   | initial frame, cells in row-major order |
+//!
+//! followed by one record per tick: u64 tick, u32 write count, then that many
+//! (u32 cell index, cell) pairs.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::buf::{BufferError, Grid, Publisher, TwoDeeBuffer, TwoDeeRead};
+use crate::snapshot::SnapshotCell;
+
+const MAGIC: [u8; 4] = *b"SLOG";
+pub const REPLAY_VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a replay log, bad magic")]
+    BadMagic,
+    #[error("Unsupported replay version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Replay holds cell type {found}, expected {expected}")]
+    WrongCellType { expected: u8, found: u8 },
+    #[error("Replay log is corrupt")]
+    Corrupt,
+    #[error("Grid of {width}x{height} is too large to record")]
+    TooLarge { width: usize, height: usize },
+    #[error(transparent)]
+    Buffer(#[from] BufferError),
+}
+
+/// Wraps a `Publisher`, logging the cells each published frame changed. Sims publish through
+/// it like any other buffer.
+pub struct Recorder<C: SnapshotCell, P: Publisher<C>, Wr: Write> {
+    buf: P,
+    width: usize,
+    height: usize,
+    /// Copy of the last published frame, which the next one is compared against.
+    last: Vec<C>,
+    writer: Wr,
+    tick: u64,
+    /// First error hit while publishing through `Publisher`, after which nothing more is
+    /// logged.
+    failed: Option<ReplayError>,
+}
+
+impl<C: SnapshotCell, P: Publisher<C>, Wr: Write> Recorder<C, P, Wr> {
+    /// Starts a log on `writer`, taking the current front frame as the initial state. `seed`
+    /// is stored for the player, so seed the sim's RNG with it.
+    pub fn new(buf: P, seed: u64, mut writer: Wr) -> Result<Self, ReplayError> {
+        let (mut width, mut height, mut last) = (0, 0, Vec::<C>::new());
+        buf.render(|f| {
+            (width, height) = (f.width(), f.height());
+            last.extend(f.rows().flatten());
+        });
+        let too_large = || ReplayError::TooLarge { width, height };
+        let w = u32::try_from(width).map_err(|_| too_large())?;
+        let h = u32::try_from(height).map_err(|_| too_large())?;
+        // Cell indices are logged as u32 as well.
+        u32::try_from(last.len().saturating_sub(1)).map_err(|_| too_large())?;
+
+        let mut out = Vec::with_capacity(24 + last.len() * C::SIZE);
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&[REPLAY_VERSION, C::TAG, 0, 0]);
+        out.extend_from_slice(&seed.to_le_bytes());
+        out.extend_from_slice(&w.to_le_bytes());
+        out.extend_from_slice(&h.to_le_bytes());
+        last.iter().for_each(|c| c.write_le(&mut out));
+        writer.write_all(&out)?;
+
+        Ok(Self {
+            buf,
+            width,
+            height,
+            last,
+            writer,
+            tick: 0,
+            failed: None,
+        })
+    }
+
+    /// Same as `Publisher::publish`, then logs the published changes as the next tick.
+    pub fn update<F: FnOnce(&mut P::Frame)>(&mut self, update_func: F) -> Result<(), ReplayError> {
+        self.step(|_, back| update_func(back))
+    }
+
+    /// Same as `Publisher::step`, then logs the published changes as the next tick.
+    pub fn step<F>(&mut self, step_func: F) -> Result<(), ReplayError>
+    where
+        F: FnOnce(&P::Frame, &mut P::Frame),
+    {
+        let (size, last) = ((self.width, self.height), &mut self.last);
+        let mut logged = Ok(vec![]);
+        self.buf.step(|front, back| {
+            step_func(front, back);
+            logged = changes(last, size, back);
+        });
+        let out = logged?;
+        self.tick += 1;
+        self.writer.write_all(&self.tick.to_le_bytes())?;
+        self.writer.write_all(&out)?;
+        Ok(())
+    }
+
+    /// Number of ticks logged so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Why logging stopped, if publishing through `Publisher` hit an error.
+    pub fn error(&self) -> Option<&ReplayError> {
+        self.failed.as_ref()
+    }
+
+    /// Pushes buffered ticks out to the writer, so a run that is killed rather than finished
+    /// still leaves a readable log.
+    pub fn flush(&mut self) -> Result<(), ReplayError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes the log and hands back the writer, or the error that stopped logging.
+    pub fn finish(mut self) -> Result<Wr, ReplayError> {
+        if let Some(e) = self.failed {
+            return Err(e);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn keep_error(&mut self, logged: Result<(), ReplayError>) {
+        if let Err(e) = logged {
+            self.failed = Some(e);
+        }
+    }
+}
+
+/// The cells of `frame` that differ from `last`, as a write count followed by (index, cell)
+/// pairs. Brings `last` up to date.
+fn changes<C, B>(last: &mut [C], size: (usize, usize), frame: &B) -> Result<Vec<u8>, ReplayError>
+where
+    C: SnapshotCell,
+    B: TwoDeeRead<C> + ?Sized,
+{
+    if (frame.width(), frame.height()) != size {
+        return Err(BufferError::SizeMismatch.into());
+    }
+    let mut out = vec![0; 4];
+    let mut count = 0u32;
+    for (index, (old, new)) in last.iter_mut().zip(frame.rows().flatten()).enumerate() {
+        if old != new {
+            let index = u32::try_from(index).map_err(|_| ReplayError::TooLarge {
+                width: size.0,
+                height: size.1,
+            })?;
+            out.extend_from_slice(&index.to_le_bytes());
+            new.write_le(&mut out);
+            *old = *new;
+            count += 1;
+        }
+    }
+    out[..4].copy_from_slice(&count.to_le_bytes());
+    Ok(out)
+}
+
+/// Publishing can't fail, so a log that can't be written is kept for `Recorder::error` and
+/// `Recorder::finish`, and the sim carries on unrecorded.
+impl<C: SnapshotCell, P: Publisher<C>, Wr: Write> Publisher<C> for Recorder<C, P, Wr> {
+    type Frame = P::Frame;
+
+    fn publish<F: FnOnce(&mut Self::Frame)>(&mut self, f: F) {
+        Publisher::step(self, |_, back| f(back));
+    }
+
+    fn step<F: FnOnce(&Self::Frame, &mut Self::Frame)>(&mut self, f: F) {
+        if self.failed.is_some() {
+            return self.buf.step(f);
+        }
+        let logged = Recorder::step(self, f);
+        self.keep_error(logged);
+    }
+
+    fn render<F: FnOnce(&Self::Frame)>(&self, f: F) {
+        self.buf.render(f);
+    }
+}
+
+impl<C: SnapshotCell, P: Publisher<C>> Recorder<C, P, BufWriter<File>> {
+    pub fn create<Pa: AsRef<Path>>(buf: P, seed: u64, path: Pa) -> Result<Self, ReplayError> {
+        Self::new(buf, seed, BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Reads a log written by `Recorder` back one tick at a time.
+pub struct Player<C: SnapshotCell, R: Read> {
+    seed: u64,
+    frame: Grid<C>,
+    tick: u64,
+    reader: R,
+}
+
+impl<C: SnapshotCell, R: Read> Player<C, R> {
+    /// Reads the header and initial frame.
+    pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        if header[4] != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(header[4]));
+        }
+        if header[5] != C::TAG {
+            return Err(ReplayError::WrongCellType {
+                expected: C::TAG,
+                found: header[5],
+            });
+        }
+        let seed = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let width = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+        let height = u32::from_le_bytes(header[20..24].try_into().unwrap()) as usize;
+        let len = width.checked_mul(height).ok_or(ReplayError::Corrupt)?;
+
+        // The header can claim any size, so the frame is read as it arrives instead of being
+        // allocated up front, and must be all there.
+        let size = len.checked_mul(C::SIZE).ok_or(ReplayError::Corrupt)?;
+        let mut cells = vec![];
+        (&mut reader).take(size as u64).read_to_end(&mut cells)?;
+        if cells.len() != size {
+            return Err(ReplayError::Corrupt);
+        }
+        let cells = cells.chunks_exact(C::SIZE).map(C::read_le).collect();
+
+        Ok(Self {
+            seed,
+            frame: Grid::from_vec(width, height, cells)?,
+            tick: 0,
+            reader,
+        })
+    }
+
+    /// The seed the recorded run was started with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Last tick applied, 0 before the first.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// State as of `tick()`.
+    pub fn frame(&self) -> &Grid<C> {
+        &self.frame
+    }
+
+    /// Applies the next tick's writes, returning its tick number, or `None` at the end of the
+    /// log.
+    pub fn step(&mut self) -> Result<Option<u64>, ReplayError> {
+        let mut tick = [0u8; 8];
+        match self.reader.read(&mut tick[..1])? {
+            0 => return Ok(None),
+            _ => self.read_exact(&mut tick[1..])?,
+        }
+        let tick = u64::from_le_bytes(tick);
+        if tick <= self.tick {
+            return Err(ReplayError::Corrupt);
+        }
+
+        let mut count = [0u8; 4];
+        self.read_exact(&mut count)?;
+        let count = u32::from_le_bytes(count) as usize;
+        // A tick writes each cell at most once.
+        if count > self.frame.cells().len() {
+            return Err(ReplayError::Corrupt);
+        }
+        let mut writes = vec![0u8; count * (4 + C::SIZE)];
+        self.read_exact(&mut writes)?;
+        let cells = self.frame.cells_mut();
+        for write in writes.chunks_exact(4 + C::SIZE) {
+            let index = u32::from_le_bytes(write[..4].try_into().unwrap()) as usize;
+            *cells.get_mut(index).ok_or(ReplayError::Corrupt)? = C::read_le(&write[4..]);
+        }
+
+        self.tick = tick;
+        Ok(Some(tick))
+    }
+
+    /// Publishes the current frame through `buf`, which must be the size of the log.
+    pub fn publish<P: Publisher<C>>(&self, buf: &mut P) -> Result<(), ReplayError> {
+        let mut size = (0, 0);
+        buf.render(|f| size = (f.width(), f.height()));
+        if (self.frame.width(), self.frame.height()) != size {
+            return Err(BufferError::SizeMismatch.into());
+        }
+        buf.publish(|back| back.copy_from(&self.frame).expect("sizes checked"));
+        Ok(())
+    }
+
+    /// A truncated record means the log was cut off mid-write.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReplayError> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => ReplayError::Corrupt,
+            _ => e.into(),
+        })
+    }
+}
+
+impl<C: SnapshotCell> Player<C, BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ReplayError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufferHandle, DoubleBuf, Heading, TurmiteRule, Turmites};
+    use rand::prelude::*;
+
+    fn record(seed: u64, ticks: usize) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let buf = DoubleBuf::<u8, 4, 3>::new();
+        let mut recorder = Recorder::new(buf.clone(), seed, vec![]).unwrap();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut frames = vec![];
+        for _ in 0..ticks {
+            recorder
+                .update(|f| {
+                    let (x, y) = (rng.gen_range(0..4), rng.gen_range(0..3));
+                    f.set(x, y, rng.gen()).unwrap();
+                })
+                .unwrap();
+            buf.render(|f| frames.push(f.buf.clone()));
+        }
+        assert_eq!(recorder.tick(), ticks as u64);
+        (recorder.finish().unwrap(), frames)
+    }
+
+    #[test]
+    fn replays_published_frames() {
+        let (log, frames) = record(7, 20);
+        let mut player = Player::<u8, _>::new(log.as_slice()).unwrap();
+        assert_eq!(player.seed(), 7);
+        assert!(player.frame().cells().iter().all(|c| *c == 0));
+
+        let mut buf = DoubleBuf::<u8, 4, 3>::new();
+        for (i, expected) in frames.iter().enumerate() {
+            assert_eq!(player.step().unwrap(), Some(i as u64 + 1));
+            player.publish(&mut buf).unwrap();
+            buf.render(|f| assert_eq!(&f.buf, expected));
+        }
+        assert_eq!(player.step().unwrap(), None);
+
+        let mut wrong_size = DoubleBuf::<u8, 3, 4>::new();
+        assert!(matches!(
+            player.publish(&mut wrong_size),
+            Err(ReplayError::Buffer(BufferError::SizeMismatch))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_logs() {
+        let (log, _) = record(1, 5);
+        assert!(matches!(
+            Player::<u8, _>::new(&b"SIMG"[..]),
+            Err(ReplayError::Io(_))
+        ));
+        let mut bad_magic = log.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            Player::<u8, _>::new(bad_magic.as_slice()),
+            Err(ReplayError::BadMagic)
+        ));
+        assert!(matches!(
+            Player::<u16, _>::new(log.as_slice()),
+            Err(ReplayError::WrongCellType {
+                expected: 2,
+                found: 1
+            })
+        ));
+
+        let mut player = Player::<u8, _>::new(&log[..log.len() - 1]).unwrap();
+        let mut result = Ok(None);
+        for _ in 0..5 {
+            result = player.step();
+        }
+        assert!(matches!(result, Err(ReplayError::Corrupt)));
+    }
+
+    #[test]
+    fn sizes_checked() {
+        let (log, _) = record(1, 1);
+        // 4x3 u8 cells follow the header, then the tick and the write count.
+        let header = 24 + 12;
+
+        // Far too big to allocate, but only as big as the log really is gets read.
+        let mut huge = log[..header].to_vec();
+        huge[16..24].copy_from_slice(&[0xff; 8]);
+        assert!(matches!(
+            Player::<u8, _>::new(huge.as_slice()),
+            Err(ReplayError::Corrupt)
+        ));
+
+        let mut too_many = log.clone();
+        too_many[header + 8..header + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut player = Player::<u8, _>::new(too_many.as_slice()).unwrap();
+        assert!(matches!(player.step(), Err(ReplayError::Corrupt)));
+    }
+
+    #[test]
+    fn records_sims_on_runtime_grids() {
+        let buf = BufferHandle::<u8>::new(6, 5);
+        let mut recorder = Recorder::new(buf.clone(), 3, vec![]).unwrap();
+        let mut ants = Turmites::new(TurmiteRule::langton()).with_ant(3, 2, Heading::North);
+        for _ in 0..12 {
+            ants.tick(&mut recorder);
+        }
+        assert_eq!(recorder.tick(), 12);
+        let log = recorder.finish().unwrap();
+
+        let mut player = Player::<u8, _>::new(log.as_slice()).unwrap();
+        while player.step().unwrap().is_some() {}
+        buf.render(|f| assert_eq!(player.frame(), f));
+
+        let mut replayed = BufferHandle::<u8>::new(6, 5);
+        player.publish(&mut replayed).unwrap();
+        replayed.render(|f| assert_eq!(player.frame(), f));
+    }
+}
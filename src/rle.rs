@@ -0,0 +1,334 @@
+//! Reading and writing the run length encoded pattern format used by Golly and most Life
+//! pattern collections, e.g.
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bo$2bo$3o!
+//! ```
+
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::buf::{Grid, TwoDeeBuffer, TwoDeeRead};
+use crate::cell::BinaryCell;
+
+/// Golly wraps body lines at 70 characters.
+const LINE_LIMIT: usize = 70;
+
+/// Most cells a header may ask for, so a bad file can't make us allocate gigabytes.
+pub const RLE_MAX_CELLS: usize = 1 << 28;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RleError {
+    #[error("Missing `x = .., y = ..` header line")]
+    MissingHeader,
+    #[error("Malformed header: {0}")]
+    BadHeader(String),
+    #[error("Unexpected {0:?} in pattern body")]
+    BadTag(char),
+    #[error("Pattern body spills outside its {width}x{height} header size")]
+    TooLarge { width: usize, height: usize },
+    #[error("Header size {width}x{height} is over the {RLE_MAX_CELLS} cell limit")]
+    HeaderTooLarge { width: usize, height: usize },
+}
+
+/// A parsed RLE pattern.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pattern {
+    /// From a `#N` line.
+    pub name: Option<String>,
+    /// From the `rule = ` header field, e.g. `B3/S23`.
+    pub rule: Option<String>,
+    /// Any other `#` lines, without the leading `#C`/`#O` etc.
+    pub comments: Vec<String>,
+    pub cells: Grid<bool>,
+}
+
+impl Pattern {
+    /// Wraps an existing grid as a pattern, so it can be written out with `to_rle`.
+    pub fn from_grid<C: BinaryCell, B: TwoDeeRead<C>>(grid: &B) -> Self {
+        let cells = grid
+            .rows()
+            .flat_map(|row| row.iter().map(|c| c.is_alive()))
+            .collect();
+        Self {
+            name: None,
+            rule: None,
+            comments: vec![],
+            cells: Grid::from_vec(grid.width(), grid.height(), cells).expect("rows match size"),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RleError> {
+        let mut name = None;
+        let mut comments = vec![];
+        let mut header = None;
+        let mut body = String::new();
+
+        for line in text.lines().map(str::trim) {
+            if header.is_some() {
+                body.push_str(line);
+                if line.contains('!') {
+                    break;
+                }
+            } else if let Some(comment) = line.strip_prefix('#') {
+                let (kind, rest) =
+                    comment.split_at(comment.chars().next().map_or(0, char::len_utf8));
+                match kind {
+                    "N" => name = Some(rest.trim().to_string()),
+                    _ => comments.push(rest.trim().to_string()),
+                }
+            } else if !line.is_empty() {
+                header = Some(parse_header(line)?);
+            }
+        }
+
+        let (width, height, rule) = header.ok_or(RleError::MissingHeader)?;
+        if width.checked_mul(height).is_none_or(|n| n > RLE_MAX_CELLS) {
+            return Err(RleError::HeaderTooLarge { width, height });
+        }
+        let mut cells = Grid::new(width, height, false);
+        let too_large = RleError::TooLarge { width, height };
+        let (mut x, mut y) = (0usize, 0usize);
+        let mut run = 0usize;
+        for ch in body.chars() {
+            match ch {
+                '0'..='9' => {
+                    // No run can be longer than the pattern is wide or tall.
+                    run = run
+                        .checked_mul(10)
+                        .and_then(|r| r.checked_add(ch.to_digit(10).unwrap() as usize))
+                        .filter(|r| *r <= width.max(height))
+                        .ok_or_else(|| too_large.clone())?;
+                }
+                '!' => break,
+                '$' => {
+                    y = y.saturating_add(run.max(1));
+                    x = 0;
+                    run = 0;
+                }
+                'b' | '.' => {
+                    x = x.saturating_add(run.max(1));
+                    run = 0;
+                }
+                // Everything else is some live state, in two-state patterns usually `o`.
+                'o' | 'A'..='Z' | 'a'..='z' => {
+                    let n = run.max(1);
+                    if y >= height || x.saturating_add(n) > width {
+                        return Err(too_large);
+                    }
+                    cells.row_mut(y)[x..x + n].fill(true);
+                    x += n;
+                    run = 0;
+                }
+                c if c.is_whitespace() => {}
+                c => return Err(RleError::BadTag(c)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            rule,
+            comments,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.height()
+    }
+
+    /// Copies the pattern onto `grid` with its top left at `at`, overwriting the cells
+    /// underneath. Parts falling off the grid are clipped.
+    pub fn place<C: BinaryCell, B: TwoDeeBuffer<C>>(&self, grid: &mut B, at: (usize, usize)) {
+        for ((x, y), alive) in self.cells.iter_coords() {
+            let (gx, gy) = (at.0 + x, at.1 + y);
+            if gx < grid.width() && gy < grid.height() {
+                grid.row_mut(gy)[gx] = C::from_alive(*alive);
+            }
+        }
+    }
+
+    /// Writes the pattern back out, wrapping body lines the way Golly does.
+    pub fn to_rle(&self) -> String {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            writeln!(out, "#N {name}").unwrap();
+        }
+        for comment in &self.comments {
+            writeln!(out, "#C {comment}").unwrap();
+        }
+        write!(out, "x = {}, y = {}", self.width(), self.height()).unwrap();
+        if let Some(rule) = &self.rule {
+            write!(out, ", rule = {rule}").unwrap();
+        }
+        out.push('\n');
+
+        let mut body = BodyWriter::default();
+        let mut pending_rows = 0;
+        for row in self.cells.rows() {
+            // Trailing dead cells are implied by the end of the row.
+            let used = row.iter().rposition(|c| *c).map_or(0, |i| i + 1);
+            if used == 0 {
+                pending_rows += 1;
+                continue;
+            }
+            body.run(pending_rows, '$');
+            pending_rows = 1;
+
+            let mut cells = row[..used].iter().peekable();
+            while let Some(&alive) = cells.next() {
+                let mut n = 1;
+                while cells.next_if(|c| **c == alive).is_some() {
+                    n += 1;
+                }
+                body.run(n, if alive { 'o' } else { 'b' });
+            }
+        }
+        body.run(1, '!');
+        out.push_str(&body.out);
+        out.push('\n');
+        out
+    }
+}
+
+/// Reads `x = 3, y = 3, rule = B3/S23`.
+fn parse_header(line: &str) -> Result<(usize, usize, Option<String>), RleError> {
+    let bad = || RleError::BadHeader(line.to_string());
+    let (mut x, mut y, mut rule) = (None, None, None);
+    for field in line.split(',') {
+        let (key, value) = field.split_once('=').ok_or_else(bad)?;
+        let value = value.trim();
+        match key.trim() {
+            "x" => x = Some(value.parse().map_err(|_| bad())?),
+            "y" => y = Some(value.parse().map_err(|_| bad())?),
+            "rule" => rule = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok((x.ok_or_else(bad)?, y.ok_or_else(bad)?, rule))
+}
+
+/// Accumulates `<count><tag>` items, breaking lines before they pass `LINE_LIMIT`.
+#[derive(Default)]
+struct BodyWriter {
+    out: String,
+    line_len: usize,
+}
+
+impl BodyWriter {
+    fn run(&mut self, n: usize, tag: char) {
+        if n == 0 {
+            return;
+        }
+        let item = if n == 1 {
+            tag.to_string()
+        } else {
+            format!("{n}{tag}")
+        };
+        if self.line_len + item.len() > LINE_LIMIT {
+            self.out.push('\n');
+            self.line_len = 0;
+        }
+        self.line_len += item.len();
+        self.out.push_str(&item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedTwoDeeBuffer;
+
+    const GLIDER: &str = "#N Glider\n#C The classic.\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    #[test]
+    fn parses_glider() {
+        let glider = Pattern::parse(GLIDER).unwrap();
+        assert_eq!(glider.name.as_deref(), Some("Glider"));
+        assert_eq!(glider.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(glider.comments, vec!["The classic."]);
+        let live: Vec<_> = glider
+            .cells
+            .iter_coords()
+            .filter(|(_, c)| **c)
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(live, vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let glider = Pattern::parse(GLIDER).unwrap();
+        assert_eq!(glider.to_rle(), GLIDER);
+
+        // Blank rows collapse into a counted `$`, and long rows wrap.
+        let text = "x = 80, y = 4\n80o2$o!\n";
+        let wide = Pattern::parse(text).unwrap();
+        let written = wide.to_rle();
+        assert!(written.lines().all(|l| l.len() <= LINE_LIMIT));
+        assert_eq!(Pattern::parse(&written).unwrap(), wide);
+        assert!(written.contains("2$o!"));
+    }
+
+    #[test]
+    fn places_at_offset() {
+        let glider = Pattern::parse(GLIDER).unwrap();
+        let mut grid = FixedTwoDeeBuffer::<u8, 5, 5>::new(0);
+        glider.place(&mut grid, (3, 3));
+        assert_eq!(grid.get(4, 3), Ok(1));
+        assert_eq!(grid.get(3, 3), Ok(0));
+        assert_eq!(Pattern::from_grid(&grid).cells.get(4, 3), Ok(true));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Pattern::parse("bo!"),
+            Err(RleError::BadHeader("bo!".into()))
+        );
+        assert_eq!(Pattern::parse("#C only"), Err(RleError::MissingHeader));
+        assert_eq!(
+            Pattern::parse("x = 2, y = 1\n3o!"),
+            Err(RleError::TooLarge {
+                width: 2,
+                height: 1
+            })
+        );
+        assert_eq!(
+            Pattern::parse("x = 2, y = 1\no?!"),
+            Err(RleError::BadTag('?'))
+        );
+    }
+
+    #[test]
+    fn hostile_input() {
+        let pattern = Pattern::parse("#é\n#Nové\nx = 1, y = 1\no!").unwrap();
+        assert_eq!(pattern.comments, vec![""]);
+        assert_eq!(pattern.name.as_deref(), Some("ové"));
+
+        let long_run = format!("x = 2, y = 2\n{}o!", "9".repeat(40));
+        assert_eq!(
+            Pattern::parse(&long_run),
+            Err(RleError::TooLarge {
+                width: 2,
+                height: 2
+            })
+        );
+        assert_eq!(
+            Pattern::parse("x = 100000, y = 100000\no!"),
+            Err(RleError::HeaderTooLarge {
+                width: 100000,
+                height: 100000
+            })
+        );
+    }
+}
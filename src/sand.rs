@@ -0,0 +1,309 @@
+//! Falling-sand style particle sim. Moving materials fall, slide off slopes and (for liquids)
+//! spread sideways, swapping with lighter materials in the way.
+
+use rand::prelude::*;
+
+use crate::buf::{Publisher, TwoDeeBuffer, TwoDeeRead};
+use crate::colour::Palette;
+use crate::pack_rgba;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Material {
+    #[default]
+    Empty,
+    Sand,
+    Water,
+    Stone,
+}
+
+impl Material {
+    pub const ALL: [Material; 4] = [
+        Material::Empty,
+        Material::Sand,
+        Material::Water,
+        Material::Stone,
+    ];
+
+    /// Reads `.` empty, `o` sand, `~` water and `#` stone.
+    pub fn parse(c: char) -> Option<Self> {
+        match c {
+            '.' | ' ' => Some(Material::Empty),
+            'o' => Some(Material::Sand),
+            '~' => Some(Material::Water),
+            '#' => Some(Material::Stone),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Material::Empty => '.',
+            Material::Sand => 'o',
+            Material::Water => '~',
+            Material::Stone => '#',
+        }
+    }
+}
+
+/// Lets a `Palette` colour materials directly.
+impl From<Material> for usize {
+    fn from(m: Material) -> usize {
+        m as usize
+    }
+}
+
+/// How one material moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaterialRule {
+    /// Falls into the cell below. Materials that don't fall never move.
+    pub falls: bool,
+    /// Slides diagonally down when the cell below is taken.
+    pub slides: bool,
+    /// Spreads sideways when it can't go down at all.
+    pub flows: bool,
+    /// Moving materials sink through lighter ones, swapping places.
+    pub density: u8,
+}
+
+impl MaterialRule {
+    pub const STATIC: Self = Self {
+        falls: false,
+        slides: false,
+        flows: false,
+        density: u8::MAX,
+    };
+
+    /// Default behaviour of each material.
+    pub fn default_for(material: Material) -> Self {
+        match material {
+            Material::Empty => Self {
+                density: 0,
+                ..Self::STATIC
+            },
+            Material::Sand => Self {
+                falls: true,
+                slides: true,
+                flows: false,
+                density: 2,
+            },
+            Material::Water => Self {
+                falls: true,
+                slides: true,
+                flows: true,
+                density: 1,
+            },
+            Material::Stone => Self::STATIC,
+        }
+    }
+}
+
+/// The particle sim. Updates run bottom up, with the order of cells in each row shuffled and
+/// left/right choices made at random so piles don't lean one way.
+#[derive(Clone, Debug)]
+pub struct FallingSand {
+    /// Indexed by `Material as usize`.
+    pub rules: [MaterialRule; 4],
+    rng: StdRng,
+    moved: Vec<bool>,
+    order: Vec<usize>,
+}
+
+impl FallingSand {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rules: Material::ALL.map(MaterialRule::default_for),
+            rng: StdRng::seed_from_u64(seed),
+            moved: vec![],
+            order: vec![],
+        }
+    }
+
+    pub fn rule(&self, material: Material) -> &MaterialRule {
+        &self.rules[material as usize]
+    }
+
+    pub fn rule_mut(&mut self, material: Material) -> &mut MaterialRule {
+        &mut self.rules[material as usize]
+    }
+
+    /// Whether `mover` may swap into a cell holding `target`.
+    fn displaces(&self, mover: Material, target: Material) -> bool {
+        let target_rule = self.rule(target);
+        target == Material::Empty
+            || (target_rule.falls && target_rule.density < self.rule(mover).density)
+    }
+
+    /// Moves every particle at most once, updating `grid` in place. Returns how many moved.
+    pub fn step_in_place<B: TwoDeeBuffer<Material> + ?Sized>(&mut self, grid: &mut B) -> usize {
+        let (w, h) = (grid.width(), grid.height());
+        self.moved.clear();
+        self.moved.resize(w * h, false);
+        let mut moves = 0;
+
+        for y in (0..h).rev() {
+            self.order.clear();
+            self.order.extend(0..w);
+            self.order.shuffle(&mut self.rng);
+            for i in 0..w {
+                let x = self.order[i];
+                if self.moved[y * w + x] {
+                    continue;
+                }
+                if let Some((tx, ty)) = self.target(grid, x, y) {
+                    let (a, b) = (grid.row(y)[x], grid.row(ty)[tx]);
+                    grid.row_mut(y)[x] = b;
+                    grid.row_mut(ty)[tx] = a;
+                    self.moved[y * w + x] = true;
+                    self.moved[ty * w + tx] = true;
+                    moves += 1;
+                }
+            }
+        }
+        moves
+    }
+
+    /// Where the particle at (x, y) goes this step, if anywhere.
+    fn target<B: TwoDeeRead<Material> + ?Sized>(
+        &mut self,
+        grid: &B,
+        x: usize,
+        y: usize,
+    ) -> Option<(usize, usize)> {
+        let material = grid.row(y)[x];
+        let rule = *self.rule(material);
+        if !rule.falls {
+            return None;
+        }
+        let (w, h) = (grid.width() as isize, grid.height() as isize);
+        let side = if self.rng.gen() { 1 } else { -1 };
+        let slides = [(side, 1), (-side, 1)].into_iter().filter(|_| rule.slides);
+        let flows = [(side, 0), (-side, 0)].into_iter().filter(|_| rule.flows);
+        let mut tries = std::iter::once((0, 1)).chain(slides).chain(flows);
+        tries.find_map(|(dx, dy)| {
+            let (tx, ty) = (x as isize + dx, y as isize + dy);
+            if !(0..w).contains(&tx) || !(0..h).contains(&ty) {
+                return None;
+            }
+            let (tx, ty) = (tx as usize, ty as usize);
+            let free =
+                !self.moved[ty * w as usize + tx] && self.displaces(material, grid.row(ty)[tx]);
+            free.then_some((tx, ty))
+        })
+    }
+
+    /// Copies the published frame to the back buffer, steps it in place and publishes.
+    pub fn tick<P: Publisher<Material>>(&mut self, buf: &mut P) {
+        buf.step(|front, back| {
+            back.copy_from(front)
+                .expect("front and back buffers are the same size");
+            self.step_in_place(back);
+        });
+    }
+
+    pub fn palette() -> Palette {
+        Palette::new(vec![
+            pack_rgba(0, 0, 0, 255),
+            pack_rgba(230, 200, 120, 255),
+            pack_rgba(40, 100, 230, 255),
+            pack_rgba(120, 120, 120, 255),
+        ])
+    }
+}
+
+/// Drops one grain of sand from `from` the Advent of Code 2022 day 14 way: straight down,
+/// else down-left, else down-right, until it comes to rest. Anything that isn't empty blocks
+/// it. Returns where it settled, or `None` if it left the grid or `from` was already taken.
+pub fn settle_grain<B: TwoDeeBuffer<Material> + ?Sized>(
+    grid: &mut B,
+    from: (usize, usize),
+) -> Option<(usize, usize)> {
+    let (mut x, mut y) = from;
+    if grid.get(x, y).ok()? != Material::Empty {
+        return None;
+    }
+    'fall: loop {
+        for dx in [0, -1, 1] {
+            let nx = x as isize + dx;
+            let below = if nx < 0 {
+                None
+            } else {
+                grid.get(nx as usize, y + 1).ok()
+            };
+            match below {
+                None => return None,
+                Some(Material::Empty) => {
+                    (x, y) = (nx as usize, y + 1);
+                    continue 'fall;
+                }
+                Some(_) => {}
+            }
+        }
+        grid.row_mut(y)[x] = Material::Sand;
+        return Some((x, y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Draw, Grid, ToText};
+
+    fn count(grid: &Grid<Material>, m: Material) -> usize {
+        grid.cells().iter().filter(|c| **c == m).count()
+    }
+
+    #[test]
+    fn sand_piles_and_sinks_through_water() {
+        let mut shaft = Grid::try_parse("#o#\n#~#\n#.#\n#.#\n###", Material::parse).unwrap();
+        let mut sim = FallingSand::new(1);
+        let steps = std::iter::repeat_with(|| sim.step_in_place(&mut shaft))
+            .take_while(|&moves| moves > 0)
+            .take(10)
+            .count();
+        assert!(steps < 10, "still moving after {steps} steps");
+        // Sand is heavier, so it ends up underneath.
+        assert_eq!(shaft.get(1, 3), Ok(Material::Sand));
+        assert_eq!(shaft.get(1, 2), Ok(Material::Water));
+
+        let mut pile = Grid::try_parse("..o..\n..o..\n..o..\n#####", Material::parse).unwrap();
+        for _ in 0..10 {
+            sim.step_in_place(&mut pile);
+        }
+        assert_eq!(count(&pile, Material::Sand), 3);
+        assert_eq!(pile.to_text(|m| m.to_char()).lines().nth(2), Some(".ooo."));
+    }
+
+    #[test]
+    fn custom_rules() {
+        let mut grid = Grid::try_parse("~....\n#####", Material::parse).unwrap();
+        let mut sim = FallingSand::new(2);
+        sim.rule_mut(Material::Water).flows = false;
+        assert_eq!(sim.step_in_place(&mut grid), 0);
+        sim.rule_mut(Material::Water).flows = true;
+        assert_eq!(sim.step_in_place(&mut grid), 1);
+        assert_eq!(grid.get(1, 0), Ok(Material::Water));
+    }
+
+    #[test]
+    fn aoc_2022_day_14_example() {
+        // Rock paths shifted left by 490.
+        let mut cave = Grid::new(20, 12, Material::Empty);
+        for path in [
+            &[(8, 4), (8, 6), (6, 6)][..],
+            &[(13, 4), (12, 4), (12, 9), (4, 9)],
+        ] {
+            for pair in path.windows(2) {
+                cave.draw_line(pair[0], pair[1], Material::Stone);
+            }
+        }
+        let grains = std::iter::from_fn(|| settle_grain(&mut cave, (10, 0))).count();
+        assert_eq!(grains, 24);
+        assert_eq!(cave.get(10, 2), Ok(Material::Sand));
+        assert_eq!(
+            cave.to_text(|m| m.to_char()).lines().nth(8),
+            Some(".....o.ooooo#.......")
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Serde support for the grid types, behind the `serde` feature.
+//!
+//! Grids serialise as `{ width, height, cells }` with cells in row-major order, so the same
+//! data loads into either a runtime `Grid` or a `FixedTwoDeeBuffer` of matching size.
+//! Turmite rules are checked as they load, the same way `TurmiteRule::from_table` checks them.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::buf::Grid;
+use crate::fixed_buf::FixedTwoDeeBuffer;
+use crate::turmite::{Transition, TurmiteRule};
+
+#[derive(Serialize)]
+struct GridRef<'a, C> {
+    width: usize,
+    height: usize,
+    cells: &'a [C],
+}
+
+#[derive(Deserialize)]
+struct GridOwned<C> {
+    width: usize,
+    height: usize,
+    cells: Vec<C>,
+}
+
+impl<C: Copy + Serialize> Serialize for Grid<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GridRef {
+            width: self.width(),
+            height: self.height(),
+            cells: self.cells(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, C: Copy + Deserialize<'de>> Deserialize<'de> for Grid<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = GridOwned::deserialize(deserializer)?;
+        Grid::from_vec(raw.width, raw.height, raw.cells).map_err(D::Error::custom)
+    }
+}
+
+impl<C: Copy + Serialize, const W: usize, const H: usize> Serialize for FixedTwoDeeBuffer<C, W, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GridRef {
+            width: W,
+            height: H,
+            cells: &self.buf,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, C: Copy + Deserialize<'de>, const W: usize, const H: usize> Deserialize<'de>
+    for FixedTwoDeeBuffer<C, W, H>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = GridOwned::deserialize(deserializer)?;
+        if (raw.width, raw.height) != (W, H) || raw.cells.len() != W * H {
+            return Err(D::Error::custom(format!(
+                "expected a {W}x{H} grid, found {}x{} with {} cells",
+                raw.width,
+                raw.height,
+                raw.cells.len()
+            )));
+        }
+        Ok(Self { buf: raw.cells })
+    }
+}
+
+#[derive(Deserialize)]
+struct TurmiteRuleOwned {
+    table: Vec<Vec<Transition>>,
+}
+
+impl<'de> Deserialize<'de> for TurmiteRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = TurmiteRuleOwned::deserialize(deserializer)?;
+        TurmiteRule::from_table(raw.table).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BufferError, Colormap, DistanceField, FixedTwoDeeBuffer, Grid, Metric, Rect, Toroidal,
+        TurmiteRule, TwoDeeRead,
+    };
+
+    #[test]
+    fn grid_json_round_trip() {
+        let grid = Grid::from_vec(2, 2, vec![1u8, 2, 3, 4]).unwrap();
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(json, r#"{"width":2,"height":2,"cells":[1,2,3,4]}"#);
+        assert_eq!(serde_json::from_str::<Grid<u8>>(&json).unwrap(), grid);
+
+        let fixed: FixedTwoDeeBuffer<u8, 2, 2> = serde_json::from_str(&json).unwrap();
+        assert_eq!(fixed.get(1, 1), Ok(4));
+        assert!(serde_json::from_str::<FixedTwoDeeBuffer<u8, 3, 1>>(&json).is_err());
+
+        let ragged = r#"{"width":3,"height":2,"cells":[1,2]}"#;
+        assert!(serde_json::from_str::<Grid<u8>>(ragged).is_err());
+    }
+
+    #[test]
+    fn distance_field_skips_scratch() {
+        let mut field = DistanceField::new(3, 2, Metric::Euclidean);
+        field.compute(&[(0, 0), (2, 1)]);
+        let json = serde_json::to_string(&field).unwrap();
+        assert!(!json.contains("envelope"));
+
+        let mut back: DistanceField = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, field);
+        back.compute(&[(1, 1)]);
+        assert_eq!(back.nearest.get(1, 0), Ok(1));
+    }
+
+    #[test]
+    fn turmite_rules_checked_on_load() {
+        let rule = TurmiteRule::langton();
+        let json = serde_json::to_string(&rule).unwrap();
+        assert_eq!(serde_json::from_str::<TurmiteRule>(&json).unwrap(), rule);
+        assert!(serde_json::from_str::<TurmiteRule>(r#"{"table":[]}"#).is_err());
+        assert!(serde_json::from_str::<TurmiteRule>(r#"{"table":[[]]}"#).is_err());
+    }
+
+    #[test]
+    fn supporting_types() {
+        let world = Toroidal::new(Grid::new(1, 1, true));
+        let json = serde_json::to_string(&world).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Toroidal<Grid<bool>>>(&json).unwrap(),
+            world
+        );
+
+        for json in [
+            serde_json::to_string(&BufferError::RaggedLine {
+                line: 1,
+                expected: 2,
+                found: 3,
+            }),
+            serde_json::to_string(&Rect::new(1, 2, 3, 4)),
+            serde_json::to_string(&Colormap::Heat),
+        ] {
+            assert!(json.is_ok());
+        }
+        let rect: Rect = serde_json::from_str(r#"{"x":1,"y":2,"width":3,"height":4}"#).unwrap();
+        assert_eq!(rect, Rect::new(1, 2, 3, 4));
+    }
+}
@@ -36,38 +36,18 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     return out;
 }
 
-fn unpack_bgra5551(packed: u32) -> vec4f {
-    // Extract each channel (5 bits each for BGR, 1 bit for A)
-    let b = (packed & 0x1Fu);         // bits 0-4
-    let g = (packed >> 5u) & 0x1Fu;   // bits 5-9
-    let r = (packed >> 10u) & 0x1Fu;  // bits 10-14
-    let a = (packed >> 15u) & 0x1u;   // bit 15
-
-    // Convert to normalized float [0,1]
-    // For 5-bit channels, divide by 31 (2^5 - 1)
-    // For 1-bit alpha, it's either 0 or 1
-    return vec4f(
-        f32(r) / 31.0,
-        f32(g) / 31.0,
-        f32(b) / 31.0,
-        f32(a)
-    );
-}
-
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let cell_size = 20.0;
     let border_size = 1.0;
     let grid_dim = vec2f(app_state.grid_dimensions);
 
-    let grid_width = f32(app_state.grid_dimensions.x);
-    let t_w = (grid_width * cell_size) + (grid_width * border_size + 1.0); // +1?
+    // Shrink cells so large (parsed) grids still fit the window, capping at 20px.
+    let fit = floor(min(app_state.dim.x / grid_dim.x, app_state.dim.y / grid_dim.y));
+    let cell_size = clamp(fit - border_size, 1.0, 20.0);
 
-    // ATTEMPT: being slick
-    //let total_dimensions = vec2(f32(app_state.grid_dimensions.x), f32(app_state.grid_dimensions.y)); // square for now.
-    let total_dimensions = vec2(t_w, t_w);
+    let total_dimensions = (grid_dim * cell_size) + (grid_dim * border_size) + 1.0; // +1?
     // We want to center the grid, so we calculate where coords should start.
-    let offset = (app_state.dim - total_dimensions) / 2.0;
+    let offset = floor((app_state.dim - total_dimensions) / 2.0);
     // Translate to grid space
     let grid_pos = in.pos.xy - offset;
     let outside = grid_pos < vec2<f32>(0.0) || grid_pos >= total_dimensions;
@@ -84,12 +64,13 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let local = grid_pos - (grid_coord * cell_and_border);
     let is_border = local < vec2(border_size, border_size);
 
-    if (any(is_border)) {
+    if (any(is_border) || any(grid_coord >= grid_dim)) {
         return vec4(1.0, 1.0, 1.0, 0.1);
     }
 
-    let grid_idx = u32(grid_coord.y * 50 + grid_coord.x);
+    let grid_idx = u32(grid_coord.y) * app_state.grid_dimensions.x + u32(grid_coord.x);
     let value = grid_data[grid_idx];
 
-    return unpack_bgra5551(value);
+    // Cells are packed by `pack_rgba`, red in the low byte.
+    return unpack4x8unorm(value);
 }
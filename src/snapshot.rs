@@ -0,0 +1,376 @@
+//! Versioned binary checkpoints of grid state.
+//!
+//! Layout, all integers little endian:
+//!
+//! | bytes | field                                   |
+//! |-------|-----------------------------------------|
+//! | 4     | magic, `SIMG`                           |
+//! | 1     | format version                          |
+//! | 1     | cell type tag, see `SnapshotCell::TAG`  |
+//! | 1     | compression, see `Compression`          |
+//! | 1     | reserved, zero                          |
+//! | 4     | width                                   |
+//! | 4     | height                                  |
+//! | ...   | cells in row-major order                |
+//!
+//! With `Compression::RunLength` the cells are stored as (u32 run length, cell) pairs.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::buf::{BufferError, BufferHandle, Grid, TwoDeeBuffer, TwoDeeRead};
+use crate::fixed_buf::DoubleBuf;
+
+const MAGIC: [u8; 4] = *b"SIMG";
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a snapshot, bad magic")]
+    BadMagic,
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Snapshot holds cell type {found}, expected {expected}")]
+    WrongCellType { expected: u8, found: u8 },
+    #[error("Unknown compression {0}")]
+    UnknownCompression(u8),
+    #[error("Snapshot data is corrupt")]
+    Corrupt,
+    #[error("{width}x{height} grid is too big for a snapshot, sides are stored as u32")]
+    TooLarge { width: usize, height: usize },
+    #[error(transparent)]
+    Buffer(#[from] BufferError),
+}
+
+/// How the cells are stored after the header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Compression {
+    #[default]
+    None = 0,
+    /// Runs of identical cells, which suits mostly empty or blocky sims.
+    RunLength = 1,
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = SnapshotError;
+
+    fn try_from(value: u8) -> Result<Self, SnapshotError> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::RunLength),
+            other => Err(SnapshotError::UnknownCompression(other)),
+        }
+    }
+}
+
+/// Cells that can be written to a snapshot. Each type gets a fixed tag so loading into the
+/// wrong type fails instead of reinterpreting bytes.
+pub trait SnapshotCell: Copy + PartialEq {
+    const TAG: u8;
+    const SIZE: usize;
+
+    fn write_le(&self, out: &mut Vec<u8>);
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! snapshot_cells {
+    ($($t:ty => $tag:literal),*) => {$(
+        impl SnapshotCell for $t {
+            const TAG: u8 = $tag;
+            const SIZE: usize = size_of::<$t>();
+
+            fn write_le(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().expect("sized by SIZE"))
+            }
+        }
+    )*};
+}
+
+snapshot_cells!(
+    u8 => 1, u16 => 2, u32 => 3, u64 => 4,
+    i8 => 5, i16 => 6, i32 => 7, i64 => 8,
+    f32 => 9, f64 => 10
+);
+
+impl SnapshotCell for bool {
+    const TAG: u8 = 11;
+    const SIZE: usize = 1;
+
+    fn write_le(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+/// Writes `grid` as a snapshot.
+pub fn write_snapshot<C, B, W>(
+    grid: &B,
+    mut writer: W,
+    compression: Compression,
+) -> Result<(), SnapshotError>
+where
+    C: SnapshotCell,
+    B: TwoDeeRead<C>,
+    W: Write,
+{
+    let (width, height) = (grid.width(), grid.height());
+    let (Ok(w), Ok(h)) = (u32::try_from(width), u32::try_from(height)) else {
+        return Err(SnapshotError::TooLarge { width, height });
+    };
+    let mut out = Vec::with_capacity(16 + width * height * C::SIZE);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&[SNAPSHOT_VERSION, C::TAG, compression as u8, 0]);
+    out.extend_from_slice(&w.to_le_bytes());
+    out.extend_from_slice(&h.to_le_bytes());
+
+    let mut cells = grid.rows().flat_map(|row| row.iter().copied());
+    match compression {
+        Compression::None => cells.for_each(|c| c.write_le(&mut out)),
+        Compression::RunLength => {
+            let Some(mut current) = cells.next() else {
+                return Ok(writer.write_all(&out)?);
+            };
+            let mut run = 1u32;
+            for c in cells {
+                if c == current && run < u32::MAX {
+                    run += 1;
+                    continue;
+                }
+                out.extend_from_slice(&run.to_le_bytes());
+                current.write_le(&mut out);
+                (current, run) = (c, 1);
+            }
+            out.extend_from_slice(&run.to_le_bytes());
+            current.write_le(&mut out);
+        }
+    }
+    Ok(writer.write_all(&out)?)
+}
+
+/// Reads a snapshot back into a runtime grid.
+pub fn read_snapshot<C: SnapshotCell, R: Read>(mut reader: R) -> Result<Grid<C>, SnapshotError> {
+    let mut header = [0u8; 16];
+    reader.read_exact(&mut header)?;
+    if header[..4] != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    if header[4] != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(header[4]));
+    }
+    if header[5] != C::TAG {
+        return Err(SnapshotError::WrongCellType {
+            expected: C::TAG,
+            found: header[5],
+        });
+    }
+    let width = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let height = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+    let len = width.checked_mul(height).ok_or(SnapshotError::Corrupt)?;
+    let compression = Compression::try_from(header[6])?;
+
+    let mut body = vec![];
+    reader.read_to_end(&mut body)?;
+    let cells = match compression {
+        Compression::None => {
+            if Some(body.len()) != len.checked_mul(C::SIZE) {
+                return Err(SnapshotError::Corrupt);
+            }
+            body.chunks_exact(C::SIZE).map(C::read_le).collect()
+        }
+        Compression::RunLength => {
+            // Sized by what the body could plausibly hold rather than the header alone, so a
+            // corrupt size can't ask for a huge allocation up front.
+            let mut cells = Vec::with_capacity(len.min(body.len()));
+            for pair in body.chunks(4 + C::SIZE) {
+                if pair.len() != 4 + C::SIZE {
+                    return Err(SnapshotError::Corrupt);
+                }
+                let run = u32::from_le_bytes(pair[..4].try_into().unwrap()) as usize;
+                if cells.len() + run > len {
+                    return Err(SnapshotError::Corrupt);
+                }
+                cells.extend(std::iter::repeat_n(C::read_le(&pair[4..]), run));
+            }
+            cells
+        }
+    };
+    Grid::from_vec(width, height, cells).map_err(|_| SnapshotError::Corrupt)
+}
+
+/// Reads a snapshot straight into an existing grid, which must be the same size. Use this to
+/// restore a `FixedTwoDeeBuffer`.
+pub fn read_snapshot_into<C, B, R>(reader: R, grid: &mut B) -> Result<(), SnapshotError>
+where
+    C: SnapshotCell,
+    B: TwoDeeBuffer<C>,
+    R: Read,
+{
+    let loaded = read_snapshot::<C, R>(reader)?;
+    Ok(grid.copy_from(&loaded)?)
+}
+
+pub fn save_snapshot<C, B, P>(
+    grid: &B,
+    path: P,
+    compression: Compression,
+) -> Result<(), SnapshotError>
+where
+    C: SnapshotCell,
+    B: TwoDeeRead<C>,
+    P: AsRef<Path>,
+{
+    let mut file = BufWriter::new(File::create(path)?);
+    write_snapshot(grid, &mut file, compression)?;
+    Ok(file.flush()?)
+}
+
+pub fn load_snapshot<C: SnapshotCell, P: AsRef<Path>>(path: P) -> Result<Grid<C>, SnapshotError> {
+    read_snapshot(BufReader::new(File::open(path)?))
+}
+
+impl<C: SnapshotCell, const W: usize, const H: usize> DoubleBuf<C, W, H> {
+    /// Writes the current front frame as a snapshot.
+    pub fn write_snapshot<Wr: Write>(
+        &self,
+        writer: Wr,
+        compression: Compression,
+    ) -> Result<(), SnapshotError> {
+        let mut result = Ok(());
+        self.render(|f| result = write_snapshot(f, writer, compression));
+        result
+    }
+
+    /// Loads a snapshot into the back buffer and publishes it as the new front frame.
+    pub fn restore_snapshot<R: Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        let loaded = read_snapshot::<C, R>(reader)?;
+        if (loaded.width(), loaded.height()) != (W, H) {
+            return Err(BufferError::SizeMismatch.into());
+        }
+        self.update(|b| b.copy_from(&loaded).expect("sizes checked"));
+        Ok(())
+    }
+}
+
+impl<C: SnapshotCell> BufferHandle<C> {
+    /// Writes the current front frame as a snapshot.
+    pub fn write_snapshot<Wr: Write>(
+        &self,
+        writer: Wr,
+        compression: Compression,
+    ) -> Result<(), SnapshotError> {
+        let mut result = Ok(());
+        self.render(|f| result = write_snapshot(f, writer, compression));
+        result
+    }
+
+    /// Loads a snapshot into the back buffer and publishes it as the new front frame.
+    pub fn restore_snapshot<R: Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        let loaded = read_snapshot::<C, R>(reader)?;
+        if (loaded.width(), loaded.height()) != (self.width(), self.height()) {
+            return Err(BufferError::SizeMismatch.into());
+        }
+        self.update(|b| b.copy_from(&loaded).expect("sizes checked"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedTwoDeeBuffer;
+
+    fn checkerboard() -> Grid<u32> {
+        let cells = (0..48).map(|i| if i < 40 { 0 } else { i % 3 }).collect();
+        Grid::from_vec(8, 6, cells).unwrap()
+    }
+
+    #[test]
+    fn round_trip_both_compressions() {
+        for compression in [Compression::None, Compression::RunLength] {
+            let mut bytes = vec![];
+            write_snapshot(&checkerboard(), &mut bytes, compression).unwrap();
+            assert_eq!(
+                read_snapshot::<u32, _>(bytes.as_slice()).unwrap(),
+                checkerboard()
+            );
+        }
+        let mut rle = vec![];
+        write_snapshot(&checkerboard(), &mut rle, Compression::RunLength).unwrap();
+        assert!(rle.len() < 16 + 48 * 4);
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        let mut bytes = vec![];
+        write_snapshot(&checkerboard(), &mut bytes, Compression::None).unwrap();
+        assert!(matches!(
+            read_snapshot::<f32, _>(bytes.as_slice()),
+            Err(SnapshotError::WrongCellType {
+                expected: 9,
+                found: 3
+            })
+        ));
+        let mut fixed = FixedTwoDeeBuffer::<u32, 4, 4>::new(0);
+        assert!(read_snapshot_into(bytes.as_slice(), &mut fixed).is_err());
+        assert!(matches!(
+            read_snapshot::<u32, _>(&b"nope and some padding"[..]),
+            Err(SnapshotError::BadMagic)
+        ));
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(
+            read_snapshot::<u32, _>(bytes.as_slice()),
+            Err(SnapshotError::Corrupt)
+        ));
+        bytes[6] = 7;
+        assert!(matches!(
+            read_snapshot::<u32, _>(bytes.as_slice()),
+            Err(SnapshotError::UnknownCompression(7))
+        ));
+    }
+
+    #[test]
+    fn sizes_checked() {
+        // A huge header over a single run.
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[SNAPSHOT_VERSION, u8::TAG, Compression::RunLength as u8, 0]);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 0, 0, 9]);
+        assert!(matches!(
+            read_snapshot::<u8, _>(bytes.as_slice()),
+            Err(SnapshotError::Corrupt)
+        ));
+
+        let wide = Grid::new(u32::MAX as usize + 1, 0, 0u8);
+        assert!(matches!(
+            write_snapshot(&wide, vec![], Compression::None),
+            Err(SnapshotError::TooLarge { height: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn double_buf_checkpoint() {
+        let mut sim = DoubleBuf::<bool, 3, 2>::new();
+        sim.update(|f| f.set(2, 1, true).unwrap());
+        let mut bytes = vec![];
+        sim.write_snapshot(&mut bytes, Compression::RunLength)
+            .unwrap();
+
+        let mut resumed = DoubleBuf::<bool, 3, 2>::new();
+        resumed.restore_snapshot(bytes.as_slice()).unwrap();
+        resumed.render(|f| assert_eq!(f.get(2, 1), Ok(true)));
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::buf::{BufferError, Grid, TwoDeeRead};
+
+impl<C: Copy> Grid<C> {
+    /// Parses an AoC style block of text, one row per line, mapping each char to a cell.
+    /// Blank lines before and after the grid are ignored; every other line must be as long
+    /// as the first.
+    pub fn parse<F: FnMut(char) -> C>(text: &str, mut cell: F) -> Result<Self, BufferError> {
+        Self::try_parse(text, |ch| Some(cell(ch)))
+    }
+
+    /// Like `parse`, but the mapping can reject chars, which fails with `UnexpectedChar`.
+    /// Errors give 1-based lines and columns of `text`, blank lines included.
+    pub fn try_parse<F: FnMut(char) -> Option<C>>(
+        text: &str,
+        mut cell: F,
+    ) -> Result<Self, BufferError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let first = lines
+            .iter()
+            .position(|l| !l.is_empty())
+            .unwrap_or(lines.len());
+        let last = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(first, |i| i + 1);
+
+        let mut width = None;
+        let mut buf = vec![];
+        for (y, line) in lines[first..last].iter().enumerate() {
+            let line_no = first + y + 1;
+            let start = buf.len();
+            for (x, ch) in line.chars().enumerate() {
+                let err = BufferError::UnexpectedChar {
+                    ch,
+                    line: line_no,
+                    column: x + 1,
+                };
+                buf.push(cell(ch).ok_or(err)?);
+            }
+            let found = buf.len() - start;
+            let expected = *width.get_or_insert(found);
+            if found != expected {
+                return Err(BufferError::RaggedLine {
+                    line: line_no,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        Grid::from_vec(width.unwrap_or(0), last - first, buf)
+    }
+}
+
+/// Printing grids back out as text, the reverse of `Grid::parse`.
+pub trait ToText<C: Copy>: TwoDeeRead<C> + Sized {
+    /// One line per row, each terminated by a newline.
+    fn to_text<F: Fn(C) -> char>(&self, ch: F) -> String {
+        self.display_with(ch).to_string()
+    }
+
+    /// Wraps the grid so it can be used with `{}`.
+    fn display_with<F: Fn(C) -> char>(&self, ch: F) -> DisplayGrid<'_, C, Self, F> {
+        DisplayGrid {
+            grid: self,
+            ch,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Copy, B: TwoDeeRead<C>> ToText<C> for B {}
+
+/// `Display` adapter returned by `ToText::display_with`.
+pub struct DisplayGrid<'a, C, B, F> {
+    grid: &'a B,
+    ch: F,
+    _marker: PhantomData<C>,
+}
+
+impl<C: Copy, B: TwoDeeRead<C>, F: Fn(C) -> char> fmt::Display for DisplayGrid<'_, C, B, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.grid.rows() {
+            for c in row {
+                write!(f, "{}", (self.ch)(*c))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Grid<char> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_with(|c| c).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Tile {
+        Wall,
+        Open,
+        Start,
+        End,
+    }
+
+    fn tile(ch: char) -> Option<Tile> {
+        match ch {
+            '#' => Some(Tile::Wall),
+            '.' => Some(Tile::Open),
+            'S' => Some(Tile::Start),
+            'E' => Some(Tile::End),
+            _ => None,
+        }
+    }
+
+    fn symbol(t: Tile) -> char {
+        match t {
+            Tile::Wall => '#',
+            Tile::Open => '.',
+            Tile::Start => 'S',
+            Tile::End => 'E',
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let input = "\n#####\n#S.E#\n#####\n";
+        let grid = Grid::try_parse(input, tile).unwrap();
+        assert_eq!((grid.width(), grid.height()), (5, 3));
+        assert_eq!(grid[(1, 1)], Tile::Start);
+        assert_eq!(grid.to_text(symbol), input.trim_start());
+    }
+
+    #[test]
+    fn ragged_and_unknown() {
+        assert_eq!(
+            Grid::parse("##\n#\n", |c| c),
+            Err(BufferError::RaggedLine {
+                line: 2,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Grid::try_parse("\n\n#.\n#?", tile),
+            Err(BufferError::UnexpectedChar {
+                ch: '?',
+                line: 4,
+                column: 2
+            })
+        );
+    }
+
+    #[test]
+    fn display_char_grid() {
+        let grid = Grid::parse("ab\ncd", |c| c).unwrap();
+        assert_eq!(format!("{grid}"), "ab\ncd\n");
+        assert!(Grid::parse("", |c| c).unwrap().is_empty());
+    }
+}
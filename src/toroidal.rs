@@ -0,0 +1,109 @@
+use num::ToPrimitive;
+
+use crate::buf::{BufferError, TwoDeeBuffer, TwoDeeRead};
+
+/// Wraps any grid so that coordinates past an edge come back around the other side,
+/// including negative ones. Handy for Life and particle sims.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Toroidal<B>(pub B);
+
+impl<B> Toroidal<B> {
+    pub fn new(inner: B) -> Self {
+        Self(inner)
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.0
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> B {
+        self.0
+    }
+}
+
+
+impl<C: Copy, B: TwoDeeRead<C>> TwoDeeRead<C> for Toroidal<B> {
+    /// Wraps x,y around the edges instead of rejecting them.
+    fn check_coords<T: ToPrimitive>(&self, x: T, y: T) -> Result<(usize, usize), BufferError> {
+        let x = x.to_isize().ok_or(BufferError::BadIndex)?;
+        let y = y.to_isize().ok_or(BufferError::BadIndex)?;
+        let (w, h) = (self.width() as isize, self.height() as isize);
+        if w == 0 || h == 0 {
+            return Err(BufferError::OutOfBounds);
+        }
+        Ok((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize))
+    }
+
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    /// Wraps `y` too. An empty grid has no rows to wrap onto, so any `y` panics as out of
+    /// range there.
+    fn row(&self, y: usize) -> &[C] {
+        self.0.row(y.checked_rem(self.height()).unwrap_or(y))
+    }
+}
+
+impl<C: Copy, B: TwoDeeBuffer<C>> TwoDeeBuffer<C> for Toroidal<B> {
+    fn row_mut(&mut self, y: usize) -> &mut [C] {
+        let y = y.checked_rem(self.height()).unwrap_or(y);
+        self.0.row_mut(y)
+    }
+
+    fn rows_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut [C]>
+    where
+        C: 'a,
+    {
+        self.0.rows_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FixedTwoDeeBuffer, Grid};
+
+    #[test]
+    fn wraps_signed_coords() {
+        let mut world = Toroidal::new(Grid::new(4, 3, 0u8));
+        world.set(-1, -1, 7).unwrap();
+        assert_eq!(world.inner().get(3, 2).unwrap(), 7);
+        assert_eq!(world.get(7, 5).unwrap(), 7);
+        assert_eq!(world.get(-5, -4).unwrap(), 7);
+    }
+
+    #[test]
+    fn wraps_fixed_buffers() {
+        let mut world = Toroidal::new(FixedTwoDeeBuffer::<u32, 5, 5>::new(0));
+        world.set(5, 0, 1).unwrap();
+        assert_eq!(world.get(0, 0).unwrap(), 1);
+        assert!(matches!(world.get(f64::NAN, 0.0), Err(BufferError::BadIndex)));
+    }
+
+    #[test]
+    fn empty_grids_hold_nothing() {
+        for (w, h) in [(0, 0), (3, 0), (0, 3)] {
+            let mut world = Toroidal::new(Grid::new(w, h, 0u8));
+            assert_eq!(world.get(3, -2), Err(BufferError::OutOfBounds));
+            assert_eq!(world.set(0, 0, 1), Err(BufferError::OutOfBounds));
+            assert_eq!(world.rows().count(), h);
+            world.fill(1);
+        }
+        // Out of range rather than a division by zero.
+        let world = Toroidal::new(Grid::new(3, 0, 0u8));
+        let err = std::panic::catch_unwind(|| world.row(5).len()).unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("out of range"), "{msg}");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::buf::{BufferError, Grid, TwoDeeBuffer, TwoDeeRead};
+use crate::rect::Rect;
+
+/// New `width` x `height` grid where each cell is read from the (x, y) `source` gives.
+fn build<C, B, F>(src: &B, width: usize, height: usize, source: F) -> Grid<C>
+where
+    C: Copy,
+    B: TwoDeeRead<C> + ?Sized,
+    F: Fn(usize, usize) -> (usize, usize),
+{
+    let mut cells = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = source(x, y);
+            cells.push(src.row(sy)[sx]);
+        }
+    }
+    Grid::from_vec(width, height, cells).expect("built to size")
+}
+
+/// Rotations and resizing for any grid, read-only views included. Each produces a new
+/// runtime `Grid`; `TransformInPlace` has the ones that rewrite a grid instead.
+pub trait Transform<C: Copy>: TwoDeeRead<C> {
+    /// Copies the contents out into a runtime grid.
+    fn to_grid(&self) -> Grid<C> {
+        let cells = self.rows().flat_map(|row| row.iter().copied()).collect();
+        Grid::from_vec(self.width(), self.height(), cells).expect("rows match dimensions")
+    }
+
+    /// Swaps rows for columns.
+    fn transpose(&self) -> Grid<C> {
+        build(self, self.height(), self.width(), |x, y| (y, x))
+    }
+
+    /// Quarter turn clockwise.
+    fn rotate_90(&self) -> Grid<C> {
+        let h = self.height();
+        build(self, h, self.width(), |x, y| (y, h - 1 - x))
+    }
+
+    /// Quarter turn anticlockwise.
+    fn rotate_270(&self) -> Grid<C> {
+        let w = self.width();
+        build(self, self.height(), w, |x, y| (w - 1 - y, x))
+    }
+
+    /// Copies out the cells inside `rect`, which must lie within the grid.
+    fn crop(&self, rect: Rect) -> Result<Grid<C>, BufferError> {
+        if rect.right() > self.width() || rect.bottom() > self.height() {
+            return Err(BufferError::OutOfBounds);
+        }
+        Ok(build(self, rect.width, rect.height, |x, y| {
+            (rect.x + x, rect.y + y)
+        }))
+    }
+
+    /// Surrounds the grid with a border `amount` cells thick, filled with `fill`.
+    fn pad(&self, amount: usize, fill: C) -> Grid<C> {
+        let (w, h) = (self.width(), self.height());
+        let mut padded = Grid::new(w + 2 * amount, h + 2 * amount, fill);
+        padded.blit(self, Rect::of_size(w, h), (amount, amount));
+        padded
+    }
+}
+
+impl<C: Copy, B: TwoDeeRead<C> + ?Sized> Transform<C> for B {}
+
+/// Flips and turns that rewrite a writable grid without allocating.
+pub trait TransformInPlace<C: Copy>: TwoDeeBuffer<C> {
+    /// Mirrors left to right, in place.
+    fn flip_horizontal(&mut self) {
+        for row in self.rows_mut() {
+            row.reverse();
+        }
+    }
+
+    /// Mirrors top to bottom, in place.
+    fn flip_vertical(&mut self) {
+        let h = self.height();
+        for y in 0..h / 2 {
+            for x in 0..self.width() {
+                let top = self.row(y)[x];
+                let bottom = self.row(h - 1 - y)[x];
+                self.row_mut(y)[x] = bottom;
+                self.row_mut(h - 1 - y)[x] = top;
+            }
+        }
+    }
+
+    /// Half turn, in place.
+    fn rotate_180(&mut self) {
+        self.flip_horizontal();
+        self.flip_vertical();
+    }
+
+    /// Transposes a square grid without allocating. Fails with `SizeMismatch` otherwise.
+    fn transpose_in_place(&mut self) -> Result<(), BufferError> {
+        if self.width() != self.height() {
+            return Err(BufferError::SizeMismatch);
+        }
+        for y in 0..self.height() {
+            for x in y + 1..self.width() {
+                let a = self.row(y)[x];
+                let b = self.row(x)[y];
+                self.row_mut(y)[x] = b;
+                self.row_mut(x)[y] = a;
+            }
+        }
+        Ok(())
+    }
+
+    /// Quarter turn clockwise of a square grid, in place. Fails with `SizeMismatch` otherwise.
+    fn rotate_90_in_place(&mut self) -> Result<(), BufferError> {
+        self.transpose_in_place()?;
+        self.flip_horizontal();
+        Ok(())
+    }
+}
+
+impl<C: Copy, B: TwoDeeBuffer<C> + ?Sized> TransformInPlace<C> for B {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FixedTwoDeeBuffer;
+
+    fn sample() -> Grid<u8> {
+        // 1 2 3
+        // 4 5 6
+        Grid::from_vec(3, 2, vec![1, 2, 3, 4, 5, 6]).unwrap()
+    }
+
+    #[test]
+    fn rotations() {
+        let grid = sample();
+        assert_eq!(grid.rotate_90().into_vec(), vec![4, 1, 5, 2, 6, 3]);
+        assert_eq!(grid.rotate_270().into_vec(), vec![3, 6, 2, 5, 1, 4]);
+        assert_eq!(grid.transpose().into_vec(), vec![1, 4, 2, 5, 3, 6]);
+        assert_eq!(grid.rotate_90().rotate_90().rotate_90(), grid.rotate_270());
+
+        let mut half = grid.clone();
+        half.rotate_180();
+        assert_eq!(half.into_vec(), vec![6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn in_place_needs_square() {
+        let mut square = FixedTwoDeeBuffer::<u8, 2, 2>::new(0);
+        square.buf = vec![1, 2, 3, 4];
+        square.rotate_90_in_place().unwrap();
+        assert_eq!(square.buf, vec![3, 1, 4, 2]);
+        assert_eq!(
+            sample().transpose_in_place(),
+            Err(BufferError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    fn crop_and_pad() {
+        let grid = sample();
+        let padded = grid.pad(1, 0);
+        assert_eq!((padded.width(), padded.height()), (5, 4));
+        assert_eq!(padded.row(1), &[0, 1, 2, 3, 0]);
+        assert_eq!(padded.crop(Rect::new(1, 1, 3, 2)).unwrap(), grid);
+        assert!(grid.crop(Rect::new(2, 0, 2, 1)).is_err());
+
+        let mut flipped = grid.clone();
+        flipped.flip_vertical();
+        assert_eq!(flipped.row(0), &[4, 5, 6]);
+
+        // Read-only views transform too.
+        let view = grid.view(Rect::new(1, 0, 2, 2)).unwrap();
+        assert_eq!(view.rotate_90().into_vec(), vec![5, 2, 6, 3]);
+        assert_eq!(
+            view.crop(Rect::new(1, 1, 1, 1)).unwrap().into_vec(),
+            vec![6]
+        );
+        assert_eq!(view.to_grid().row(0), &[2, 3]);
+    }
+}
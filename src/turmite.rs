@@ -0,0 +1,441 @@
+//! Langton's ant and its relatives: ants with more colours, and turmites, which also carry an
+//! internal state that picks how they react to each colour.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::buf::{Publisher, TwoDeeBuffer};
+use crate::colour::{Colormap, Palette};
+use crate::life::RuleError;
+use crate::neighbours::Edge;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    /// Grid step for moving one cell this way, y pointing down.
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Heading::North => (0, -1),
+            Heading::East => (1, 0),
+            Heading::South => (0, 1),
+            Heading::West => (-1, 0),
+        }
+    }
+
+    pub fn turn(self, turn: Turn) -> Self {
+        const CLOCKWISE: [Heading; 4] =
+            [Heading::North, Heading::East, Heading::South, Heading::West];
+        let quarter_turns = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+        CLOCKWISE[(self as usize + quarter_turns) % 4]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Turn {
+    Left,
+    Right,
+    None,
+    UTurn,
+}
+
+/// What an ant in one state does on one colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transition {
+    /// Colour left on the cell.
+    pub write: u8,
+    pub turn: Turn,
+    /// State the ant moves into.
+    pub next: usize,
+}
+
+/// What an ant does for each of its states and each colour it stands on.
+///
+/// Parses from a string of turns for single state ants, one letter per colour, each cell
+/// moving on to the next colour: `RL` is Langton's ant, turning right on colour 0 and left on
+/// colour 1. Letters are `L`, `R`, `N` (no turn) and `U` (U-turn).
+///
+/// Turmites with more states use the usual table notation, one group per state holding a
+/// `{write, turn, next}` triple per colour, with turns coded 1 for none, 2 right, 4 U-turn and
+/// 8 left. `{{{1,2,0},{0,8,0}}}` is Langton's ant again.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TurmiteRule {
+    /// `table[state][colour]`. Every state covers the same colours.
+    table: Vec<Vec<Transition>>,
+}
+
+impl TurmiteRule {
+    /// Cells hold `u8` colours, so rules can't use more than this many.
+    pub const MAX_COLOURS: usize = u8::MAX as usize + 1;
+
+    pub fn langton() -> Self {
+        Self::from_turns(&[Turn::Right, Turn::Left]).expect("two colours")
+    }
+
+    /// A single state ant turning by `turns[colour]`, which cycles colours as it goes.
+    pub fn from_turns(turns: &[Turn]) -> Result<Self, RuleError> {
+        let colours = turns.len();
+        if !(1..=Self::MAX_COLOURS).contains(&colours) {
+            return Err(RuleError::BadColours(colours));
+        }
+        let state = turns
+            .iter()
+            .enumerate()
+            .map(|(colour, &turn)| Transition {
+                write: ((colour + 1) % colours) as u8,
+                turn,
+                next: 0,
+            })
+            .collect();
+        Ok(Self { table: vec![state] })
+    }
+
+    /// A rule from `table[state][colour]`. There must be at least one state, every state must
+    /// cover the same 1 to 256 colours, and every transition must write one of those colours
+    /// and move to one of those states.
+    pub fn from_table(table: Vec<Vec<Transition>>) -> Result<Self, RuleError> {
+        let colours = table.first().map_or(0, Vec::len);
+        if !(1..=Self::MAX_COLOURS).contains(&colours) {
+            return Err(RuleError::BadColours(colours));
+        }
+        let fits = |t: &Transition| (t.write as usize) < colours && t.next < table.len();
+        if table
+            .iter()
+            .any(|state| state.len() != colours || !state.iter().all(fits))
+        {
+            return Err(RuleError::BadTable);
+        }
+        Ok(Self { table })
+    }
+
+    pub fn parse(rule: &str) -> Result<Self, RuleError> {
+        let malformed = || RuleError::Malformed(rule.to_string());
+        if rule.trim_start().starts_with('{') {
+            return Self::from_table(parse_table(rule).ok_or_else(malformed)?);
+        }
+        let turns = rule
+            .trim()
+            .chars()
+            .map(|c| match c.to_ascii_uppercase() {
+                'L' => Ok(Turn::Left),
+                'R' => Ok(Turn::Right),
+                'N' => Ok(Turn::None),
+                'U' => Ok(Turn::UTurn),
+                _ => Err(malformed()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_turns(&turns)
+    }
+
+    /// `table()[state][colour]` is what an ant in `state` does on `colour`.
+    pub fn table(&self) -> &[Vec<Transition>] {
+        &self.table
+    }
+
+    /// Number of colours cells cycle through.
+    pub fn colours(&self) -> usize {
+        self.table.first().map_or(0, Vec::len)
+    }
+
+    pub fn states(&self) -> usize {
+        self.table.len()
+    }
+
+    /// The turns of a single state ant that cycles colours, which can be written as letters.
+    fn turns(&self) -> Option<Vec<Turn>> {
+        let [state] = self.table.as_slice() else {
+            return None;
+        };
+        let cycles = state
+            .iter()
+            .enumerate()
+            .all(|(colour, t)| t.write as usize == (colour + 1) % state.len() && t.next == 0);
+        cycles.then(|| state.iter().map(|t| t.turn).collect())
+    }
+}
+
+/// Reads `{{{write,turn,next},...},...}`, not yet checking the table is consistent.
+fn parse_table(rule: &str) -> Option<Vec<Vec<Transition>>> {
+    let compact: String = rule.chars().filter(|c| !c.is_whitespace()).collect();
+    let inner = compact.strip_prefix("{{{")?.strip_suffix("}}}")?;
+    inner
+        .split("}},{{")
+        .map(|state| {
+            state
+                .split("},{")
+                .map(|triple| {
+                    let fields: Vec<usize> = triple
+                        .split(',')
+                        .map(|n| n.parse().ok())
+                        .collect::<Option<_>>()?;
+                    let [write, turn, next] = fields[..] else {
+                        return None;
+                    };
+                    let turn = match turn {
+                        1 => Turn::None,
+                        2 => Turn::Right,
+                        4 => Turn::UTurn,
+                        8 => Turn::Left,
+                        _ => return None,
+                    };
+                    Some(Transition {
+                        write: u8::try_from(write).ok()?,
+                        turn,
+                        next,
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl FromStr for TurmiteRule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for TurmiteRule {
+    /// Letters when they can say it, otherwise the table.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(turns) = self.turns() {
+            for turn in turns {
+                let c = match turn {
+                    Turn::Left => 'L',
+                    Turn::Right => 'R',
+                    Turn::None => 'N',
+                    Turn::UTurn => 'U',
+                };
+                write!(f, "{c}")?;
+            }
+            return Ok(());
+        }
+
+        write!(f, "{{")?;
+        for (i, state) in self.table.iter().enumerate() {
+            write!(f, "{}{{", if i > 0 { "," } else { "" })?;
+            for (j, t) in state.iter().enumerate() {
+                let turn = match t.turn {
+                    Turn::None => 1,
+                    Turn::Right => 2,
+                    Turn::UTurn => 4,
+                    Turn::Left => 8,
+                };
+                let sep = if j > 0 { "," } else { "" };
+                write!(f, "{sep}{{{},{turn},{}}}", t.write, t.next)?;
+            }
+            write!(f, "}}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ant {
+    pub x: usize,
+    pub y: usize,
+    pub heading: Heading,
+    /// Row of the rule's table the ant is following.
+    pub state: usize,
+}
+
+/// A set of ants sharing one rule, walking over a grid of `u8` colours. Each ant looks up its
+/// state and the colour under it in the rule, writes the new colour, turns and steps forward.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Turmites {
+    pub rule: TurmiteRule,
+    pub ants: Vec<Ant>,
+    /// What happens to ants walking off the grid: `Wrap` brings them round the other side,
+    /// `Clamp` holds them at the border, and `Skip`/`Constant` take them off the grid.
+    pub edge: Edge<u8>,
+}
+
+impl Turmites {
+    /// No ants yet, on a wrapping world.
+    pub fn new(rule: TurmiteRule) -> Self {
+        Self {
+            rule,
+            ants: vec![],
+            edge: Edge::Wrap,
+        }
+    }
+
+    pub fn with_ant(mut self, x: usize, y: usize, heading: Heading) -> Self {
+        self.ants.push(Ant {
+            x,
+            y,
+            heading,
+            state: 0,
+        });
+        self
+    }
+
+    pub fn with_edge(mut self, edge: Edge<u8>) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Moves every ant once, in order, editing `grid` in place. Ants that aren't on the grid
+    /// are dropped.
+    pub fn step_in_place<B: TwoDeeBuffer<u8> + ?Sized>(&mut self, grid: &mut B) {
+        let (w, h) = (grid.width(), grid.height());
+        let (colours, states) = (self.rule.colours(), self.rule.states());
+        let (rule, edge) = (&self.rule, self.edge);
+        self.ants.retain_mut(|ant| {
+            let Ok(colour) = grid.get(ant.x, ant.y) else {
+                return false;
+            };
+            let t = rule.table[ant.state % states][colour as usize % colours];
+            ant.heading = ant.heading.turn(t.turn);
+            ant.state = t.next;
+            grid.row_mut(ant.y)[ant.x] = t.write;
+
+            let (dx, dy) = ant.heading.delta();
+            match edge.resolve(ant.x as isize + dx, ant.y as isize + dy, w, h) {
+                Some((x, y)) => {
+                    (ant.x, ant.y) = (x, y);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Copies the published frame to the back buffer, moves the ants over it and publishes.
+    pub fn tick<P: Publisher<u8>>(&mut self, buf: &mut P) {
+        buf.step(|front, back| {
+            back.copy_from(front)
+                .expect("front and back buffers are the same size");
+            self.step_in_place(back);
+        });
+    }
+
+    /// Black for colour 0, then the rest of the colours spread across `Colormap::Heat`.
+    pub fn palette(&self) -> Palette {
+        let mut palette = Palette::from_colormap(Colormap::Heat, self.rule.colours() + 1);
+        palette.colours.remove(1);
+        palette
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DoubleBuf, Grid, TwoDeeRead};
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!("rl".parse(), Ok(TurmiteRule::langton()));
+        assert_eq!(TurmiteRule::parse("LLRRNU").unwrap().to_string(), "LLRRNU");
+        assert!(TurmiteRule::parse("").is_err());
+        assert!(TurmiteRule::parse(&"RL".repeat(128)).is_ok());
+        assert!(TurmiteRule::parse(&"RL".repeat(129)).is_err());
+        assert_eq!(
+            TurmiteRule::parse("RX"),
+            Err(RuleError::Malformed("RX".into()))
+        );
+        assert_eq!(Heading::West.turn(Turn::Right), Heading::North);
+        assert_eq!(Heading::North.turn(Turn::Left), Heading::West);
+    }
+
+    #[test]
+    fn langtons_ant_first_steps() {
+        let mut ants = Turmites::new(TurmiteRule::langton()).with_ant(5, 5, Heading::North);
+        let mut buf = DoubleBuf::<u8, 10, 10>::new();
+        for _ in 0..5 {
+            ants.tick(&mut buf);
+        }
+        buf.render(|f| {
+            let black: Vec<_> = f
+                .iter_coords()
+                .filter(|(_, c)| **c == 1)
+                .map(|(p, _)| p)
+                .collect();
+            assert_eq!(black, vec![(6, 5), (5, 6), (6, 6)]);
+        });
+        assert_eq!(
+            ants.ants,
+            vec![Ant {
+                x: 4,
+                y: 5,
+                heading: Heading::West,
+                state: 0
+            }]
+        );
+        assert_eq!(ants.palette().colours.len(), 2);
+    }
+
+    #[test]
+    fn turmite_tables() {
+        let langton = TurmiteRule::parse("{{{1, 2, 0}, {0, 8, 0}}}").unwrap();
+        assert_eq!(langton, TurmiteRule::langton());
+        assert_eq!(langton.to_string(), "RL");
+
+        let fibonacci = "{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}";
+        let rule = TurmiteRule::parse(fibonacci).unwrap();
+        assert_eq!((rule.states(), rule.colours()), (2, 2));
+        assert_eq!(rule.to_string(), fibonacci);
+
+        // Writes a colour the rule doesn't have, goes to a missing state, ragged, bad turn.
+        assert!(TurmiteRule::parse("{{{2,2,0},{0,8,0}}}").is_err());
+        assert!(TurmiteRule::parse("{{{1,2,1},{0,8,0}}}").is_err());
+        assert!(TurmiteRule::parse("{{{1,8,1},{1,8,1}},{{1,2,1}}}").is_err());
+        assert!(TurmiteRule::parse("{{{1,3,0},{0,8,0}}}").is_err());
+
+        assert_eq!(TurmiteRule::from_turns(&[]), Err(RuleError::BadColours(0)));
+        assert_eq!(
+            TurmiteRule::from_turns(&[Turn::Left; 257]),
+            Err(RuleError::BadColours(257))
+        );
+        assert_eq!(
+            TurmiteRule::from_table(vec![]),
+            Err(RuleError::BadColours(0))
+        );
+    }
+
+    #[test]
+    fn turmites_change_state() {
+        let rule = TurmiteRule::parse("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}").unwrap();
+        let mut ants = Turmites::new(rule).with_ant(2, 2, Heading::North);
+        let mut grid = Grid::new(5, 5, 0u8);
+        // State 0 on colour 0: paint it, turn left and switch to state 1.
+        ants.step_in_place(&mut grid);
+        assert_eq!(grid.get(2, 2), Ok(1));
+        assert_eq!((ants.ants[0].x, ants.ants[0].state), (1, 1));
+        // State 1 on colour 0: paint it, turn right and stay in state 1.
+        ants.step_in_place(&mut grid);
+        assert_eq!(grid.get(1, 2), Ok(1));
+        assert_eq!(ants.ants[0].heading, Heading::North);
+        assert_eq!(ants.ants[0].state, 1);
+    }
+
+    #[test]
+    fn ants_fall_off_unwrapped_edges() {
+        let mut grid = Grid::new(2, 2, 0u8);
+        let mut ants = Turmites::new(TurmiteRule::parse("N").unwrap())
+            .with_ant(0, 0, Heading::East)
+            .with_edge(Edge::Skip);
+        ants.step_in_place(&mut grid);
+        assert_eq!(ants.ants.len(), 1);
+        ants.step_in_place(&mut grid);
+        assert!(ants.ants.is_empty());
+    }
+}
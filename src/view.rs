@@ -0,0 +1,293 @@
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+use crate::buf::{BufferError, TwoDeeBuffer, TwoDeeRead};
+use crate::rect::Rect;
+
+/// Checks `rect` fits in a `width` x `height` parent, returning the offset of its first cell
+/// in the parent's row-major storage.
+fn offset_of(rect: Rect, stride: usize, width: usize, height: usize) -> Result<usize, BufferError> {
+    if rect.right() > width || rect.bottom() > height {
+        return Err(BufferError::OutOfBounds);
+    }
+    Ok(rect.y * stride + rect.x)
+}
+
+/// Read-only window onto part of a grid. Coordinates are local to the window.
+///
+/// Implements `TwoDeeRead`, so anything that only reads a grid works on it too.
+#[derive(Clone, Copy, Debug)]
+pub struct View<'a, C> {
+    cells: &'a [C],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, C: Copy> View<'a, C> {
+    /// Window onto `rect` of row-major `cells`, where each row is `stride` cells long.
+    pub fn new(cells: &'a [C], stride: usize, rect: Rect) -> Result<Self, BufferError> {
+        let height = cells.len().checked_div(stride).unwrap_or(0);
+        let start = offset_of(rect, stride, stride, height)?;
+        let cells = if rect.is_empty() {
+            &[]
+        } else {
+            &cells[start..]
+        };
+        Ok(Self {
+            cells,
+            stride,
+            width: rect.width,
+            height: if rect.width == 0 { 0 } else { rect.height },
+        })
+    }
+
+    /// Narrower window, relative to this one.
+    pub fn view(&self, rect: Rect) -> Result<View<'a, C>, BufferError> {
+        let start = offset_of(rect, self.stride, self.width, self.height)?;
+        Ok(Self {
+            cells: if rect.is_empty() {
+                &[]
+            } else {
+                &self.cells[start..]
+            },
+            stride: self.stride,
+            width: rect.width,
+            height: if rect.width == 0 { 0 } else { rect.height },
+        })
+    }
+}
+
+impl<C: Copy> TwoDeeRead<C> for View<'_, C> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[C] {
+        assert!(y < self.height, "row {y} out of range");
+        &self.cells[y * self.stride..y * self.stride + self.width]
+    }
+}
+
+/// Mutable window onto part of a grid. Coordinates are local to the window.
+///
+/// Several can exist over the same parent at once as long as they don't overlap, which is
+/// what `tiles` hands out for processing regions in parallel.
+#[derive(Debug)]
+pub struct ViewMut<'a, C> {
+    ptr: *mut C,
+    stride: usize,
+    width: usize,
+    height: usize,
+    _marker: PhantomData<&'a mut [C]>,
+}
+
+// SAFETY: A ViewMut is an exclusive borrow of its cells, same as &mut [C].
+unsafe impl<C: Send> Send for ViewMut<'_, C> {}
+unsafe impl<C: Sync> Sync for ViewMut<'_, C> {}
+
+impl<'a, C: Copy> ViewMut<'a, C> {
+    /// Window onto `rect` of row-major `cells`, where each row is `stride` cells long.
+    pub fn new(cells: &'a mut [C], stride: usize, rect: Rect) -> Result<Self, BufferError> {
+        let height = cells.len().checked_div(stride).unwrap_or(0);
+        let start = offset_of(rect, stride, stride, height)?;
+        Ok(Self {
+            // SAFETY: offset_of checked the rect lies within cells.
+            ptr: unsafe { cells.as_mut_ptr().add(start.min(cells.len())) },
+            stride,
+            width: rect.width,
+            height: if rect.width == 0 { 0 } else { rect.height },
+            _marker: PhantomData,
+        })
+    }
+
+    /// Read-only window, relative to this one.
+    pub fn view(&self, rect: Rect) -> Result<View<'_, C>, BufferError> {
+        let start = offset_of(rect, self.stride, self.width, self.height)?;
+        let len = self.span();
+        // SAFETY: ptr..ptr+len are the cells this view borrows, and we're borrowed shared.
+        let cells = unsafe { std::slice::from_raw_parts(self.ptr, len) };
+        Ok(View {
+            cells: if rect.is_empty() {
+                &[]
+            } else {
+                &cells[start..]
+            },
+            stride: self.stride,
+            width: rect.width,
+            height: if rect.width == 0 { 0 } else { rect.height },
+        })
+    }
+
+    /// Narrower mutable window, relative to this one.
+    pub fn view_mut(&mut self, rect: Rect) -> Result<ViewMut<'_, C>, BufferError> {
+        self.reborrow().sub(rect)
+    }
+
+    /// Splits into disjoint tiles of up to `tile_width` x `tile_height`, row by row. Tiles on
+    /// the right and bottom edges are smaller when the size doesn't divide evenly.
+    pub fn tiles(self, tile_width: usize, tile_height: usize) -> Vec<ViewMut<'a, C>> {
+        assert!(
+            tile_width > 0 && tile_height > 0,
+            "tiles must be at least 1x1"
+        );
+        let mut tiles = vec![];
+        for y in (0..self.height).step_by(tile_height) {
+            for x in (0..self.width).step_by(tile_width) {
+                let w = tile_width.min(self.width - x);
+                let h = tile_height.min(self.height - y);
+                tiles.push(ViewMut {
+                    // SAFETY: (x, y) lies within this view, and tiles never overlap.
+                    ptr: unsafe { self.ptr.add(y * self.stride + x) },
+                    stride: self.stride,
+                    width: w,
+                    height: h,
+                    _marker: PhantomData,
+                });
+            }
+        }
+        tiles
+    }
+
+    fn reborrow(&mut self) -> ViewMut<'_, C> {
+        ViewMut {
+            ptr: self.ptr,
+            stride: self.stride,
+            width: self.width,
+            height: self.height,
+            _marker: PhantomData,
+        }
+    }
+
+    fn sub(self, rect: Rect) -> Result<ViewMut<'a, C>, BufferError> {
+        let start = offset_of(rect, self.stride, self.width, self.height)?;
+        Ok(ViewMut {
+            // SAFETY: offset_of checked the rect lies within this view.
+            ptr: if rect.is_empty() {
+                self.ptr
+            } else {
+                unsafe { self.ptr.add(start) }
+            },
+            stride: self.stride,
+            width: rect.width,
+            height: if rect.width == 0 { 0 } else { rect.height },
+            _marker: PhantomData,
+        })
+    }
+
+    /// Number of parent cells between our first and last cell, inclusive.
+    fn span(&self) -> usize {
+        match self.height {
+            0 => 0,
+            h => (h - 1) * self.stride + self.width,
+        }
+    }
+}
+
+impl<C: Copy> TwoDeeRead<C> for ViewMut<'_, C> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[C] {
+        assert!(y < self.height, "row {y} out of range");
+        // SAFETY: Row y of this view, which we borrow.
+        unsafe { std::slice::from_raw_parts(self.ptr.add(y * self.stride), self.width) }
+    }
+}
+
+impl<C: Copy> TwoDeeBuffer<C> for ViewMut<'_, C> {
+    fn row_mut(&mut self, y: usize) -> &mut [C] {
+        assert!(y < self.height, "row {y} out of range");
+        // SAFETY: Row y of this view, which we borrow exclusively.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.add(y * self.stride), self.width) }
+    }
+
+    fn rows_mut<'b>(&'b mut self) -> impl Iterator<Item = &'b mut [C]>
+    where
+        C: 'b,
+    {
+        let (ptr, stride, width) = (self.ptr, self.stride, self.width);
+        // SAFETY: Each row is handed out once, and rows never overlap.
+        (0..self.height)
+            .map(move |y| unsafe { std::slice::from_raw_parts_mut(ptr.add(y * stride), width) })
+    }
+}
+
+macro_rules! index_impls {
+    ($($view:ident),*) => {$(
+        impl<C: Copy> Index<(usize, usize)> for $view<'_, C> {
+            type Output = C;
+
+            fn index(&self, (x, y): (usize, usize)) -> &C {
+                &self.row(y)[x]
+            }
+        }
+    )*};
+}
+
+index_impls!(View, ViewMut);
+
+impl<C: Copy> IndexMut<(usize, usize)> for ViewMut<'_, C> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut C {
+        &mut self.row_mut(y)[x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{label_components, Connectivity, FixedTwoDeeBuffer, Grid};
+
+    #[test]
+    fn local_coordinates() {
+        let grid = Grid::from_vec(4, 3, (0..12).collect()).unwrap();
+        let view = grid.view(Rect::new(1, 1, 2, 2)).unwrap();
+        assert_eq!(view.get(0, 0), Ok(5));
+        assert_eq!(view.row(1), &[9, 10]);
+        assert_eq!(view.get(2, 0), Err(BufferError::OutOfBounds));
+        assert_eq!(view.view(Rect::new(1, 1, 1, 1)).unwrap()[(0, 0)], 10);
+        assert!(grid.view(Rect::new(3, 0, 2, 1)).is_err());
+        // Read-side algorithms take views like any other grid.
+        assert_eq!(label_components(&view, Connectivity::Four, |_| true).len(), 4);
+    }
+
+    #[test]
+    fn mutable_view_writes_through() {
+        let mut buf = FixedTwoDeeBuffer::<u8, 5, 5>::new(0);
+        let mut room = buf.view_mut(Rect::new(1, 1, 3, 3)).unwrap();
+        room.fill(1);
+        room.view_mut(Rect::new(1, 1, 1, 1))
+            .unwrap()
+            .set(0, 0, 2)
+            .unwrap();
+        assert_eq!(buf.get(0, 0), Ok(0));
+        assert_eq!(buf.get(3, 3), Ok(1));
+        assert_eq!(buf.get(2, 2), Ok(2));
+        assert_eq!(buf.get(4, 4), Ok(0));
+    }
+
+    #[test]
+    fn parallel_tiles() {
+        let mut grid = Grid::new(10, 7, 0usize);
+        let tiles = grid.tiles_mut(4, 3);
+        assert_eq!(tiles.len(), 9);
+        std::thread::scope(|s| {
+            for (i, mut tile) in tiles.into_iter().enumerate() {
+                s.spawn(move || tile.fill(i + 1));
+            }
+        });
+        assert_eq!(grid.get(0, 0), Ok(1));
+        assert_eq!(grid.get(9, 0), Ok(3));
+        assert_eq!(grid.get(4, 3), Ok(5));
+        assert_eq!(grid.get(9, 6), Ok(9));
+    }
+}